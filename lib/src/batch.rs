@@ -0,0 +1,137 @@
+use crate::config::TreeConfig;
+use crate::hasher::{Hash, TreeHasher, EMPTY_HASH};
+use crate::smtverifier::{leaf_hash, VerifyError};
+use crate::{verify_circom_proof, MerkleProof};
+use alloc::collections::{BTreeMap, BTreeSet};
+
+/// Checks that two trees differ at most in the keys of the given proofs, as arbo's
+/// `CheckProofBatch` does.
+///
+/// `old_proofs` and `new_proofs` hold one proof (inclusion or non-inclusion) per key, in
+/// the same order, against the old and the new root respectively. Every subtree and leaf
+/// that is not on the path of one of those keys must be present in both trees.
+pub fn check_proof_batch<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    old_proofs: &[MerkleProof],
+    new_proofs: &[MerkleProof],
+) -> Result<(), VerifyError> {
+    if old_proofs.is_empty() || old_proofs.len() != new_proofs.len() {
+        return Err(VerifyError::InvalidBatch);
+    }
+
+    let mut keys = BTreeSet::new();
+    for (old, new) in old_proofs.iter().zip(new_proofs) {
+        if old.key != new.key || !keys.insert(old.key) {
+            return Err(VerifyError::InvalidBatch);
+        }
+    }
+
+    for proofs in [old_proofs, new_proofs] {
+        if proofs.iter().any(|p| p.root != proofs[0].root) {
+            return Err(VerifyError::InvalidBatch);
+        }
+        for proof in proofs {
+            verify_circom_proof(config, proof)?;
+        }
+    }
+
+    let mut old = frontier(config, old_proofs, &keys)?;
+    let mut new = frontier(config, new_proofs, &keys)?;
+    if !old.take_moved_leaves(&mut new)
+        || !new.take_moved_leaves(&mut old)
+        || old.nodes != new.nodes
+    {
+        return Err(VerifyError::UnexpectedChange);
+    }
+
+    Ok(())
+}
+
+/// What a tree holds outside the paths of the batch keys. Together with the batch keys, it
+/// accounts for every leaf in the tree.
+struct Frontier {
+    /// The subtrees that hang from the paths, by their (level, prefix) position.
+    nodes: BTreeMap<(usize, Hash), Hash>,
+    /// The leaves the paths end in when they belong to keys outside the batch, by key.
+    leaves: BTreeMap<Hash, Hash>,
+}
+
+impl Frontier {
+    /// Removes from `other` the leaves of `self` that `other` holds as a subtree instead.
+    /// A lone leaf is the only thing allowed to move: it is pushed down or pulled up along
+    /// its own path when a batch key next to it is inserted or deleted, and its hash stays
+    /// the same. Returns false if one of the leaves is in neither form.
+    fn take_moved_leaves(&self, other: &mut Frontier) -> bool {
+        for (key, hash) in &self.leaves {
+            if let Some(other_hash) = other.leaves.get(key) {
+                if other_hash != hash {
+                    return false;
+                }
+                continue;
+            }
+            let moved = other
+                .nodes
+                .iter()
+                .find(|(&(depth, node_prefix), node)| {
+                    *node == hash && prefix(key, depth) == node_prefix
+                })
+                .map(|(&position, _)| position);
+            match moved {
+                Some(position) => other.nodes.remove(&position),
+                None => return false,
+            };
+        }
+        true
+    }
+}
+
+/// Returns the [`Frontier`] of the tree the proofs were taken from.
+fn frontier<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proofs: &[MerkleProof],
+    keys: &BTreeSet<Hash>,
+) -> Result<Frontier, VerifyError> {
+    let depth = |p: &MerkleProof| {
+        p.siblings
+            .iter()
+            .rposition(|s| *s != EMPTY_HASH)
+            .map_or(0, |i| i + 1)
+    };
+
+    let on_path: BTreeSet<(usize, Hash)> = proofs
+        .iter()
+        .flat_map(|p| (0..=depth(p)).map(|d| (d, prefix(&p.key, d))))
+        .collect();
+
+    let mut frontier = Frontier {
+        nodes: BTreeMap::new(),
+        leaves: BTreeMap::new(),
+    };
+    for p in proofs {
+        for (i, sibling) in p.siblings[..depth(p)].iter().enumerate() {
+            let mut position = (i + 1, prefix(&p.key, i + 1));
+            if let Some(byte) = position.1.get_mut(i / 8) {
+                *byte ^= 1 << (i % 8);
+            }
+            if *sibling != EMPTY_HASH && !on_path.contains(&position) {
+                frontier.nodes.insert(position, *sibling);
+            }
+        }
+        if p.fnc && !p.is_old_0 && !keys.contains(&p.old_key) {
+            let hash = leaf_hash(&config.hasher, &p.old_key, &p.old_value, config.key_len)?;
+            frontier.leaves.insert(p.old_key, hash);
+        }
+    }
+    Ok(frontier)
+}
+
+/// Returns the first `depth` bits of `key`, the position of its path at that depth.
+fn prefix(key: &Hash, depth: usize) -> Hash {
+    let depth = depth.min(key.len() * 8);
+    let mut prefix = EMPTY_HASH;
+    prefix[..depth / 8].copy_from_slice(&key[..depth / 8]);
+    if depth % 8 != 0 {
+        prefix[depth / 8] = key[depth / 8] & ((1 << (depth % 8)) - 1);
+    }
+    prefix
+}
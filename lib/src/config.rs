@@ -0,0 +1,65 @@
+use crate::hasher::{Blake3, Hash, EMPTY_HASH};
+use crate::smtverifier::{check_key_width, VerifyError};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// The shape of the tree proofs are checked against, as given to arbo's `NewTree`.
+///
+/// The verifier doesn't infer it from the proofs: a proof may have fewer siblings than
+/// levels (they are taken as empty), but not more, and its keys must fit in `key_len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeConfig<H = Blake3> {
+    /// Maximum depth of the tree, arbo's `MaxLevels`.
+    pub max_levels: usize,
+    /// Number of key bytes hashed in the leaves, at most 32.
+    pub key_len: usize,
+    pub hasher: H,
+}
+
+impl<H> TreeConfig<H> {
+    /// Returns the configuration of a tree with `max_levels` levels and keys just long
+    /// enough to address all of them, as arbo sizes them.
+    pub fn new(max_levels: usize, hasher: H) -> Self {
+        TreeConfig {
+            max_levels,
+            key_len: max_levels.div_ceil(8),
+            hasher,
+        }
+    }
+
+    /// Checks the configuration, the number of `siblings` and the width of `keys`,
+    /// returning the siblings padded with empty ones up to `max_levels`.
+    pub(crate) fn check_proof(
+        &self,
+        siblings: &[Hash],
+        keys: &[&Hash],
+    ) -> Result<Vec<Hash>, VerifyError> {
+        self.check_keys(keys)?;
+        if siblings.len() > self.max_levels {
+            return Err(VerifyError::TooManySiblings {
+                siblings: siblings.len(),
+                max_levels: self.max_levels,
+            });
+        }
+
+        let mut padded = siblings.to_vec();
+        padded.resize(self.max_levels, EMPTY_HASH);
+        Ok(padded)
+    }
+
+    /// Checks the configuration and the width of `keys`.
+    pub(crate) fn check_keys(&self, keys: &[&Hash]) -> Result<(), VerifyError> {
+        let max_key_len = EMPTY_HASH.len();
+        if self.max_levels == 0
+            || self.max_levels > max_key_len * 8
+            || self.key_len == 0
+            || self.key_len > max_key_len
+        {
+            return Err(VerifyError::InvalidConfig {
+                max_levels: self.max_levels,
+                key_len: self.key_len,
+            });
+        }
+        check_key_width(keys, self.key_len)
+    }
+}
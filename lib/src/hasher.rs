@@ -0,0 +1,179 @@
+use crate::smtverifier::VerifyError;
+use serde::{Deserialize, Serialize};
+
+/// A node hash, little-endian as arbo stores it. All of arbo's hash functions output
+/// 32 bytes.
+pub type Hash = [u8; 32];
+
+/// The empty node.
+pub const EMPTY_HASH: Hash = [0u8; 32];
+
+/// The hash function used to build the tree, equivalent to arbo's `HashFunction`.
+///
+/// Empty nodes are represented by [`EMPTY_HASH`]. Hashes over a field reject inputs
+/// that don't fit in it with [`VerifyError::InvalidHashInput`].
+pub trait TreeHasher {
+    /// Name of the hash function, committed in the public values. It is the one returned
+    /// by arbo's `Type()` for the hash functions arbo has.
+    const NAME: &'static str;
+
+    /// Hash of a leaf, arbo's `Hash(key, value, 1)`.
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError>;
+
+    /// Hash of an intermediate node, arbo's `Hash(left, right)`.
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError>;
+}
+
+/// Blake3, arbo's `HashFunctionBlake3`. This is the default hasher.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blake3;
+
+impl TreeHasher for Blake3 {
+    const NAME: &'static str = "blake3";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(key);
+        hasher.update(value);
+        hasher.update(&[1u8]);
+        Ok(hasher.finalize().into())
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(left);
+        hasher.update(right);
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// SHA-256, arbo's `HashFunctionSha256`.
+#[cfg(feature = "sha256")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sha256;
+
+#[cfg(feature = "sha256")]
+impl TreeHasher for Sha256 {
+    const NAME: &'static str = "sha256";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        use sha2::Digest;
+        Ok(sha2::Sha256::new()
+            .chain_update(key)
+            .chain_update(value)
+            .chain_update([1u8])
+            .finalize()
+            .into())
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        use sha2::Digest;
+        Ok(sha2::Sha256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into())
+    }
+}
+
+/// Keccak-256 (the Ethereum variant, not SHA3-256).
+#[cfg(feature = "keccak256")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keccak256;
+
+#[cfg(feature = "keccak256")]
+impl TreeHasher for Keccak256 {
+    const NAME: &'static str = "keccak256";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        use sha3::Digest;
+        Ok(sha3::Keccak256::new()
+            .chain_update(key)
+            .chain_update(value)
+            .chain_update([1u8])
+            .finalize()
+            .into())
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        use sha3::Digest;
+        Ok(sha3::Keccak256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into())
+    }
+}
+
+/// Blake2b with a 256-bit output, arbo's `HashFunctionBlake2b`.
+#[cfg(feature = "blake2b")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blake2b;
+
+#[cfg(feature = "blake2b")]
+type Blake2b256 = blake2::Blake2b<blake2::digest::consts::U32>;
+
+#[cfg(feature = "blake2b")]
+impl TreeHasher for Blake2b {
+    const NAME: &'static str = "blake2b";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        use blake2::Digest;
+        Ok(Blake2b256::new()
+            .chain_update(key)
+            .chain_update(value)
+            .chain_update([1u8])
+            .finalize()
+            .into())
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        use blake2::Digest;
+        Ok(Blake2b256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into())
+    }
+}
+
+/// Poseidon over BN254 with circom's parameters, arbo's `HashFunctionPoseidon`.
+///
+/// As in arbo, every input is read as a little-endian number, so trailing zero bytes
+/// don't change it and an empty input is 0. Inputs must be smaller than the BN254 scalar
+/// field modulus.
+#[cfg(feature = "poseidon")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Poseidon;
+
+#[cfg(feature = "poseidon")]
+impl Poseidon {
+    fn hash(inputs: &[&[u8]]) -> Result<Hash, VerifyError> {
+        use light_poseidon::PoseidonBytesHasher;
+        let mut elements = alloc::vec![[0u8; 32]; inputs.len()];
+        for (element, input) in elements.iter_mut().zip(inputs) {
+            let len = input.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            if len > element.len() {
+                return Err(VerifyError::InvalidHashInput);
+            }
+            element[..len].copy_from_slice(&input[..len]);
+        }
+        let elements: alloc::vec::Vec<&[u8]> = elements.iter().map(|e| &e[..]).collect();
+        light_poseidon::Poseidon::<ark_bn254::Fr>::new_circom(inputs.len())
+            .and_then(|mut hasher| hasher.hash_bytes_le(&elements))
+            .map_err(|_| VerifyError::InvalidHashInput)
+    }
+}
+
+#[cfg(feature = "poseidon")]
+impl TreeHasher for Poseidon {
+    const NAME: &'static str = "poseidon";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        Self::hash(&[key, value, &[1u8]])
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        Self::hash(&[left, right])
+    }
+}
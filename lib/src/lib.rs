@@ -1,43 +1,374 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloy_primitives::{B256, U256};
 use alloy_sol_types::sol;
+use core::str::FromStr;
 use num_bigint::BigUint;
-use num_traits::Zero;
-use serde::{Deserialize, Deserializer, Serialize};
-use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+pub mod batch;
+pub mod config;
+pub mod hasher;
+pub mod multiproof;
+pub mod output;
+pub mod siblings;
+pub mod smtprocessor;
 pub mod smtverifier;
+#[cfg(feature = "snark")]
+pub mod snark;
+pub mod tree;
+
+pub use batch::check_proof_batch;
+pub use config::TreeConfig;
+pub use hasher::{Blake3, Hash, TreeHasher, EMPTY_HASH};
+pub use multiproof::{verify_multiproof, MultiProof, MultiProofLeaf};
+pub use output::{OutputMode, PublicValuesError, Statement};
+pub use siblings::{pack_siblings, unpack_siblings, PackError};
+pub use smtprocessor::Operation;
+pub use smtverifier::{VerificationTrace, VerifyError};
+#[cfg(feature = "snark")]
+pub use snark::{verify_snark, ProofSystem, SnarkError};
+pub use tree::{Tree, TreeError};
 
 sol! {
+    /// The tree the proofs were verified against: arbo's `MaxLevels`, the key length in
+    /// bytes and the name of the hash function. The guest reads it from its stdin, so it
+    /// has to be committed for the public values to mean anything.
+    #[derive(Debug, PartialEq, Eq)]
+    struct TreeConfigSol {
+        uint256 maxLevels;
+        uint256 keyLen;
+        string hasher;
+    }
+
     /// Sub-struct for Merkle proof details
     struct MerkleProofSol {
         uint256 root;
         uint256 key;
-        uint256 value;
+        bytes value; // empty for non-inclusion proofs
         uint256[] siblings; // Dynamic array of uint256 for siblings
+        bool fnc; // false: inclusion, true: non-inclusion
     }
 
     /// The public values encoded as a struct that can be easily deserialized inside Solidity.
     struct PublicValuesStruct {
+        TreeConfigSol config;
         MerkleProofSol proof;  // Nested struct
     }
+
+    /// The public values of `OutputMode::RootKey`: `key` is (or is not) in the tree, with a
+    /// value that is kept private.
+    struct RootKeyPublicValuesStruct {
+        TreeConfigSol config;
+        uint256 root;
+        uint256 key;
+        bool fnc;
+    }
+
+    /// The public values of `OutputMode::RootValueHash`: some key holds a value with this
+    /// keccak256 hash. The hash is unsalted, so it reveals values that can be guessed.
+    struct RootValueHashPublicValuesStruct {
+        TreeConfigSol config;
+        uint256 root;
+        bytes32 valueHash;
+        bool fnc;
+    }
+
+    /// The public values of `OutputMode::Root`: a valid proof exists for this root.
+    struct RootPublicValuesStruct {
+        TreeConfigSol config;
+        uint256 root;
+    }
+
+    /// The public values of the batch program: the trees with these roots, both with
+    /// `config`, only differ in `keys`.
+    struct ProofBatchPublicValuesStruct {
+        TreeConfigSol config;
+        uint256 oldRoot;
+        uint256 newRoot;
+        uint256[] keys;
+    }
+
+    /// The public values of the aggregation program: the public values of every proof of
+    /// the program with verifying key `vkey` it verified, in order.
+    struct AggregationPublicValuesStruct {
+        bytes32 vkey;
+        bytes[] publicValues;
+    }
+
+    /// The public values of the chain program: the tree, with `config`, went from
+    /// `genesisRoot` to `root` in `steps` batches of transitions, each one proven by the
+    /// chain program with verifying key `vkey`.
+    struct ChainPublicValuesStruct {
+        bytes32 vkey;
+        TreeConfigSol config;
+        uint256 genesisRoot;
+        uint256 root;
+        uint256 steps;
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Converts a root or key into a `uint256` for the public values.
+pub fn hash_to_u256(h: &Hash) -> U256 {
+    U256::from_le_bytes(*h)
+}
+
+/// Converts the `hash_u32` of a verifying key into a `bytes32` for the public values, as
+/// big-endian words like the SDK's `hash_bytes`.
+pub fn vkey_to_b256(vkey: &[u32; 8]) -> B256 {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(vkey) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    B256::from(bytes)
+}
+
+/// A Merkle proof as consumed by the verifier, covering both inclusion and
+/// non-inclusion proofs. This is the Rust counterpart of arbo's `CircomVerifierProof`.
+///
+/// Roots, keys and siblings are little-endian 32-byte words, so the verifier doesn't
+/// need big integers. Values are the bytes stored in the leaf, which can be longer than
+/// the key. Siblings are serialized packed, so empty levels don't take space in the
+/// zkVM stdin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MerkleProof {
-    pub root: BigUint,
-    pub key: BigUint,
-    pub value: BigUint,
-    pub siblings: Vec<BigUint>,
+    pub root: Hash,
+    pub key: Hash,
+    pub value: Vec<u8>,
+    #[serde(with = "siblings::packed")]
+    pub siblings: Vec<Hash>,
+    /// false: inclusion, true: non-inclusion
+    pub fnc: bool,
+    pub is_old_0: bool,
+    pub old_key: Hash,
+    pub old_value: Vec<u8>,
+}
+
+pub type CircomVerifierProof = MerkleProof;
+
+impl MerkleProof {
+    /// Builds an inclusion proof from siblings packed with arbo's `PackSiblings`,
+    /// padding them with empty siblings up to `levels`.
+    pub fn from_packed_siblings(
+        root: Hash,
+        key: Hash,
+        value: Vec<u8>,
+        packed_siblings: &[u8],
+        levels: usize,
+    ) -> Result<Self, PackError> {
+        Ok(MerkleProof {
+            root,
+            key,
+            value,
+            siblings: siblings::unpack_padded_siblings(packed_siblings, levels)?,
+            fnc: false,
+            is_old_0: false,
+            old_key: EMPTY_HASH,
+            old_value: Vec::new(),
+        })
+    }
+
+    /// Returns the siblings packed as arbo's `PackSiblings` does.
+    pub fn packed_siblings(&self) -> Result<Vec<u8>, PackError> {
+        pack_siblings(&self.siblings)
+    }
+}
+
+/// A proof that applying `op` on `new_key` moves the tree from `old_root` to `new_root`.
+/// See [`smtprocessor::verify_transition`] for the meaning of each field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitionProof {
+    pub old_root: Hash,
+    pub new_root: Hash,
+    #[serde(with = "siblings::packed")]
+    pub siblings: Vec<Hash>,
+    pub old_key: Hash,
+    pub old_value: Vec<u8>,
+    pub is_old_0: bool,
+    pub new_key: Hash,
+    pub new_value: Vec<u8>,
+    pub op: Operation,
 }
+
+/// The JSON format emitted by arbo's `GenerateCircomVerifierProof`, where every number
+/// is a decimal string. This is the only place where proofs use big integers.
+///
+/// Fields are kept in alphabetical order, as Go's `json.Marshal` sorts map keys, so that
+/// serializing with serde_json yields the same bytes as arbo.
+///
+/// Values are numbers in the JSON, so their length is lost: by default they are taken
+/// as long as the key or their significant bytes, whichever is longer. `valueLen` and
+/// `oldValueLen`, which arbo doesn't emit, give the length explicitly, as needed for
+/// values that end in zero bytes (e.g. gob-encoded data).
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MerkleProofFromFile {
-    #[serde(deserialize_with = "string_to_biguint")]
-    pub root: BigUint,
-    #[serde(deserialize_with = "string_to_biguint")]
+    #[serde(serialize_with = "bool_to_number", deserialize_with = "number_to_bool")]
+    pub fnc: bool,
+    #[serde(
+        rename = "isOld0",
+        serialize_with = "bool_to_string",
+        deserialize_with = "string_to_bool"
+    )]
+    pub is_old_0: bool,
+    #[serde(
+        serialize_with = "biguint_to_string",
+        deserialize_with = "string_to_biguint"
+    )]
     pub key: BigUint,
-    #[serde(deserialize_with = "string_to_biguint")]
-    pub value: BigUint,
-    #[serde(deserialize_with = "vec_string_to_biguint")]
+    #[serde(
+        serialize_with = "biguint_to_string",
+        deserialize_with = "string_to_biguint"
+    )]
+    pub old_key: BigUint,
+    #[serde(
+        serialize_with = "biguint_to_string",
+        deserialize_with = "string_to_biguint"
+    )]
+    pub old_value: BigUint,
+    /// The length of `oldValue` in bytes, like `valueLen`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_value_len: Option<usize>,
+    #[serde(
+        serialize_with = "biguint_to_string",
+        deserialize_with = "string_to_biguint"
+    )]
+    pub root: BigUint,
+    #[serde(
+        serialize_with = "vec_biguint_to_string",
+        deserialize_with = "vec_string_to_biguint"
+    )]
     pub siblings: Vec<BigUint>,
+    #[serde(
+        serialize_with = "biguint_to_string",
+        deserialize_with = "string_to_biguint"
+    )]
+    pub value: BigUint,
+    /// The length of `value` in bytes. arbo never writes it, so it has to be added by hand
+    /// to its output whenever the value may end in a zero byte, as every gob stream does:
+    /// without it, the files written by `main.go` for keys 4 and 5 don't verify.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_len: Option<usize>,
+}
+
+impl TryFrom<MerkleProofFromFile> for MerkleProof {
+    type Error = VerifyError;
+
+    /// Fails with [`VerifyError::Overflow`] if a root, key or sibling doesn't fit in 32
+    /// bytes, or a value doesn't fit in its explicit length.
+    fn try_from(proof: MerkleProofFromFile) -> Result<Self, Self::Error> {
+        let siblings: Vec<Hash> = proof
+            .siblings
+            .iter()
+            .map(biguint_to_hash)
+            .collect::<Result<_, _>>()?;
+        let key_len = json_key_len(&siblings);
+        Ok(MerkleProof {
+            root: biguint_to_hash(&proof.root)?,
+            key: biguint_to_hash(&proof.key)?,
+            value: biguint_to_value(&proof.value, proof.value_len, key_len)?,
+            siblings,
+            fnc: proof.fnc,
+            is_old_0: proof.is_old_0,
+            old_key: biguint_to_hash(&proof.old_key)?,
+            old_value: biguint_to_value(&proof.old_value, proof.old_value_len, key_len)?,
+        })
+    }
+}
+
+impl From<MerkleProof> for MerkleProofFromFile {
+    fn from(proof: MerkleProof) -> Self {
+        let to_biguint = |h: &[u8]| BigUint::from_bytes_le(h);
+        let key_len = json_key_len(&proof.siblings);
+        MerkleProofFromFile {
+            fnc: proof.fnc,
+            is_old_0: proof.is_old_0,
+            key: to_biguint(&proof.key),
+            old_key: to_biguint(&proof.old_key),
+            old_value: to_biguint(&proof.old_value),
+            old_value_len: explicit_value_len(&proof.old_value, key_len),
+            root: to_biguint(&proof.root),
+            siblings: proof.siblings.iter().map(|s| to_biguint(s)).collect(),
+            value: to_biguint(&proof.value),
+            value_len: explicit_value_len(&proof.value, key_len),
+        }
+    }
+}
+
+/// Key length used to size the values of a JSON proof. arbo writes one sibling per
+/// level, so this is the key length of [`TreeConfig::new`].
+fn json_key_len<T>(siblings: &[T]) -> usize {
+    siblings.len().div_ceil(8)
+}
+
+/// Returns the bytes of the value `i`, `len` bytes long if given, or else as long as the
+/// key or its significant bytes, whichever is longer.
+pub(crate) fn biguint_to_value(
+    i: &BigUint,
+    len: Option<usize>,
+    key_len: usize,
+) -> Result<Vec<u8>, VerifyError> {
+    let mut bytes = i.to_bytes_le();
+    // zero is encoded as a single zero byte
+    bytes.truncate(significant_len(&bytes));
+    let len = len.unwrap_or(bytes.len().max(key_len));
+    if bytes.len() > len {
+        return Err(VerifyError::Overflow);
+    }
+    bytes.resize(len, 0u8);
+    Ok(bytes)
+}
+
+/// Returns the length of `value` if [`biguint_to_value`] wouldn't guess it.
+fn explicit_value_len(value: &[u8], key_len: usize) -> Option<usize> {
+    let default_len = significant_len(value).max(key_len);
+    (value.len() != default_len).then_some(value.len())
+}
+
+fn significant_len(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)
+}
+
+pub(crate) fn biguint_to_hash(i: &BigUint) -> Result<Hash, VerifyError> {
+    let bytes = i.to_bytes_le();
+    let mut hash = EMPTY_HASH;
+    hash.get_mut(..bytes.len())
+        .ok_or(VerifyError::Overflow)?
+        .copy_from_slice(&bytes);
+    Ok(hash)
+}
+
+fn biguint_to_string<S>(i: &BigUint, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&i.to_string())
+}
+
+fn vec_biguint_to_string<S>(v: &[BigUint], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(v.iter().map(|i| i.to_string()))
+}
+
+fn bool_to_number<S>(b: &bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u8(*b as u8)
+}
+
+fn bool_to_string<S>(b: &bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(if *b { "1" } else { "0" })
 }
 
 fn string_to_biguint<'de, D>(deserializer: D) -> Result<BigUint, D::Error>
@@ -48,6 +379,36 @@ where
     BigUint::from_str(&s).map_err(serde::de::Error::custom)
 }
 
+fn number_to_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let n: u8 = Deserialize::deserialize(deserializer)?;
+    match n {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(serde::de::Error::custom(format!(
+            "expected 0 or 1, got {}",
+            n
+        ))),
+    }
+}
+
+fn string_to_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    match s.as_str() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(serde::de::Error::custom(format!(
+            "expected \"0\" or \"1\", got {:?}",
+            s
+        ))),
+    }
+}
+
 fn vec_string_to_biguint<'de, D>(deserializer: D) -> Result<Vec<BigUint>, D::Error>
 where
     D: Deserializer<'de>,
@@ -59,16 +420,159 @@ where
         .collect()
 }
 
-pub fn verify(expected_root: &BigUint, key: &BigUint, value: &BigUint, siblings: Vec<BigUint>) {
+pub fn verify<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    expected_root: &Hash,
+    key: &Hash,
+    value: &[u8],
+    siblings: &[Hash],
+) -> Result<(), VerifyError> {
     smtverifier::verify_extended(
+        config,
         true,
         expected_root,
-        &BigUint::zero(),
-        &BigUint::zero(),
+        &EMPTY_HASH,
+        &[],
         false,
         key,
         value,
         false,
         siblings,
-    );
+    )
+}
+
+/// Verifies that `key` is not in the tree with root `expected_root`.
+///
+/// `old_key` and `old_value` are the leaf found at the position where `key` would be,
+/// or `is_old_0` is set if that position is empty.
+pub fn verify_non_inclusion<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    expected_root: &Hash,
+    key: &Hash,
+    old_key: &Hash,
+    old_value: &[u8],
+    is_old_0: bool,
+    siblings: &[Hash],
+) -> Result<(), VerifyError> {
+    smtverifier::verify_extended(
+        config,
+        true,
+        expected_root,
+        old_key,
+        old_value,
+        is_old_0,
+        key,
+        &[],
+        true,
+        siblings,
+    )
+}
+
+/// Verifies an inclusion or non-inclusion proof, as selected by its `fnc` field.
+pub fn verify_circom_proof<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &MerkleProof,
+) -> Result<(), VerifyError> {
+    if proof.fnc {
+        verify_non_inclusion(
+            config,
+            &proof.root,
+            &proof.key,
+            &proof.old_key,
+            &proof.old_value,
+            proof.is_old_0,
+            &proof.siblings,
+        )
+    } else {
+        verify(
+            config,
+            &proof.root,
+            &proof.key,
+            &proof.value,
+            &proof.siblings,
+        )
+    }
+}
+
+/// Returns the root implied by the key, value (or old leaf) and siblings of `proof`,
+/// without comparing it to `proof.root`. The root is little-endian, as arbo stores it.
+///
+/// This is the root that [`verify_circom_proof`] checks `proof.root` against, so it can
+/// be used to derive the root after changing a value, or to debug a mismatch.
+pub fn compute_root<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &MerkleProof,
+) -> Result<Hash, VerifyError> {
+    smtverifier::compute_root_extended(
+        config,
+        true,
+        &proof.old_key,
+        &proof.old_value,
+        proof.is_old_0,
+        &proof.key,
+        &proof.value,
+        proof.fnc,
+        &proof.siblings,
+    )
+}
+
+/// Returns the steps taken to compute the root of `proof`, level by level, so that a
+/// failing proof can be inspected. Like [`compute_root`], it doesn't check `proof.root`.
+pub fn trace_circom_proof<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &MerkleProof,
+) -> Result<VerificationTrace, VerifyError> {
+    smtverifier::trace_extended(
+        config,
+        true,
+        &proof.old_key,
+        &proof.old_value,
+        proof.is_old_0,
+        &proof.key,
+        &proof.value,
+        proof.fnc,
+        &proof.siblings,
+    )
+}
+
+/// Verifies a state transition (insert, update or delete) between two roots.
+pub fn verify_transition<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &TransitionProof,
+) -> Result<(), VerifyError> {
+    smtprocessor::verify_transition(
+        config,
+        &proof.old_root,
+        &proof.new_root,
+        &proof.siblings,
+        &proof.old_key,
+        &proof.old_value,
+        proof.is_old_0,
+        &proof.new_key,
+        &proof.new_value,
+        proof.op,
+    )
+}
+
+/// Applies `transitions` in order starting from the tree with root `root`, verifying each
+/// one, and returns the root of the resulting tree.
+///
+/// Every transition must start from the root the previous one ended in.
+pub fn apply_transitions<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    root: &Hash,
+    transitions: &[TransitionProof],
+) -> Result<Hash, VerifyError> {
+    let mut root = *root;
+    for transition in transitions {
+        if transition.old_root != root {
+            return Err(VerifyError::RootMismatch {
+                expected: root,
+                computed: transition.old_root,
+            });
+        }
+        verify_transition(config, transition)?;
+        root = transition.new_root;
+    }
+    Ok(root)
 }
@@ -0,0 +1,208 @@
+use crate::config::TreeConfig;
+use crate::hasher::{Hash, TreeHasher, EMPTY_HASH};
+use crate::smtverifier::{key_bit, leaf_hash, VerifyError};
+use crate::MerkleProof;
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::slice;
+use serde::{Deserialize, Serialize};
+
+/// Proofs for several keys of the same tree, sharing the nodes their paths have in
+/// common.
+///
+/// Instead of one list of siblings per key, it holds the nodes hanging from the union
+/// of the paths, in depth-first order (left before right), so that every node is stored
+/// once and hashed once. Nodes on the path of another key are computed, not stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof {
+    pub root: Hash,
+    pub leaves: Vec<MultiProofLeaf>,
+    pub nodes: Vec<Hash>,
+}
+
+/// A key proven by a [`MultiProof`], with the same meaning as the fields of a
+/// [`MerkleProof`] of that key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProofLeaf {
+    pub key: Hash,
+    pub value: Vec<u8>,
+    /// false: inclusion, true: non-inclusion
+    pub fnc: bool,
+    pub is_old_0: bool,
+    pub old_key: Hash,
+    pub old_value: Vec<u8>,
+    /// Level of the leaf (or empty node) where the path of `key` ends.
+    pub depth: usize,
+}
+
+impl MultiProof {
+    /// Merges `proofs`, which must be against the same root and for distinct keys, into
+    /// a multiproof. The proofs are not verified, that is left to [`verify_multiproof`].
+    pub fn from_proofs(proofs: &[MerkleProof]) -> Result<Self, VerifyError> {
+        let root = proofs.first().ok_or(VerifyError::InvalidMultiProof)?.root;
+        let mut keys = BTreeSet::new();
+        if proofs.iter().any(|p| p.root != root || !keys.insert(p.key)) {
+            return Err(VerifyError::InvalidMultiProof);
+        }
+
+        let leaves: Vec<MultiProofLeaf> = proofs
+            .iter()
+            .map(|p| MultiProofLeaf {
+                key: p.key,
+                value: p.value.clone(),
+                fnc: p.fnc,
+                is_old_0: p.is_old_0,
+                old_key: p.old_key,
+                old_value: p.old_value.clone(),
+                depth: depth(&p.siblings),
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        let paths: Vec<(&MultiProofLeaf, &[Hash])> = leaves
+            .iter()
+            .zip(proofs)
+            .map(|(leaf, p)| (leaf, p.siblings.as_slice()))
+            .collect();
+        collect_nodes(0, &paths, &mut nodes);
+
+        Ok(MultiProof {
+            root,
+            leaves,
+            nodes,
+        })
+    }
+}
+
+/// Verifies every leaf of `proof` against its root.
+pub fn verify_multiproof<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &MultiProof,
+) -> Result<(), VerifyError> {
+    let computed = compute_multiproof_root(config, proof)?;
+    if computed != proof.root {
+        return Err(VerifyError::RootMismatch {
+            expected: proof.root,
+            computed,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the root implied by the leaves and nodes of `proof`, without comparing it to
+/// `proof.root`.
+pub fn compute_multiproof_root<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    proof: &MultiProof,
+) -> Result<Hash, VerifyError> {
+    if proof.leaves.is_empty() {
+        return Err(VerifyError::InvalidMultiProof);
+    }
+    let mut keys = BTreeSet::new();
+    if !proof.leaves.iter().all(|l| keys.insert(l.key)) {
+        return Err(VerifyError::InvalidMultiProof);
+    }
+    let all_keys: Vec<&Hash> = proof
+        .leaves
+        .iter()
+        .flat_map(|l| [&l.key, &l.old_key])
+        .collect();
+    config.check_keys(&all_keys)?;
+
+    let leaves: Vec<&MultiProofLeaf> = proof.leaves.iter().collect();
+    let mut nodes = proof.nodes.iter();
+    let root = subtree_root(config, 0, &leaves, &mut nodes)?;
+    if nodes.next().is_some() {
+        return Err(VerifyError::InvalidMultiProof);
+    }
+    Ok(root)
+}
+
+/// Computes the hash of the node at level `lvl` that the paths of `leaves` go through,
+/// taking the hashes of the subtrees none of them goes into from `nodes`.
+fn subtree_root<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    lvl: usize,
+    leaves: &[&MultiProofLeaf],
+    nodes: &mut slice::Iter<Hash>,
+) -> Result<Hash, VerifyError> {
+    if leaves.iter().any(|l| l.depth == lvl) {
+        // every path that goes through a leaf or empty node ends in it, so they must
+        // all agree on what it is
+        let end = end_node(leaves[0])?;
+        if leaves[0].depth != lvl {
+            return Err(VerifyError::InvalidMultiProof);
+        }
+        for leaf in &leaves[1..] {
+            if leaf.depth != lvl || end_node(leaf)? != end {
+                return Err(VerifyError::InvalidMultiProof);
+            }
+        }
+        return match end {
+            Some((key, value)) => leaf_hash(&config.hasher, key, value, config.key_len),
+            None => Ok(EMPTY_HASH),
+        };
+    }
+    if lvl >= config.max_levels {
+        return Err(VerifyError::InvalidMultiProof);
+    }
+
+    let (right, left): (Vec<&MultiProofLeaf>, Vec<&MultiProofLeaf>) =
+        leaves.iter().partition(|l| key_bit(&l.key, lvl));
+    let l = child_root(config, lvl + 1, &left, nodes)?;
+    let r = child_root(config, lvl + 1, &right, nodes)?;
+    config.hasher.node_hash(&l, &r)
+}
+
+fn child_root<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    lvl: usize,
+    leaves: &[&MultiProofLeaf],
+    nodes: &mut slice::Iter<Hash>,
+) -> Result<Hash, VerifyError> {
+    if leaves.is_empty() {
+        nodes.next().copied().ok_or(VerifyError::InvalidMultiProof)
+    } else {
+        subtree_root(config, lvl, leaves, nodes)
+    }
+}
+
+/// Returns the key and value of the leaf where the path of `leaf` ends, or `None` if it
+/// ends in an empty node.
+fn end_node(leaf: &MultiProofLeaf) -> Result<Option<(&Hash, &[u8])>, VerifyError> {
+    if !leaf.fnc {
+        Ok(Some((&leaf.key, &leaf.value)))
+    } else if leaf.is_old_0 {
+        Ok(None)
+    } else if leaf.old_key == leaf.key {
+        Err(VerifyError::FncOldKeyConflict)
+    } else {
+        Ok(Some((&leaf.old_key, &leaf.old_value)))
+    }
+}
+
+/// Appends the siblings of `paths` below level `lvl` that are not on another path, in
+/// the order [`subtree_root`] takes them.
+fn collect_nodes(lvl: usize, paths: &[(&MultiProofLeaf, &[Hash])], nodes: &mut Vec<Hash>) {
+    if paths.iter().any(|(leaf, _)| leaf.depth == lvl) {
+        return;
+    }
+    let (right, left): (Vec<(&MultiProofLeaf, &[Hash])>, Vec<_>) =
+        paths.iter().partition(|(l, _)| key_bit(&l.key, lvl));
+    for (side, other) in [(&left, &right), (&right, &left)] {
+        if side.is_empty() {
+            nodes.push(other[0].1[lvl]);
+        } else {
+            collect_nodes(lvl + 1, side, nodes);
+        }
+    }
+}
+
+/// Level where the circom verifier places the leaf: right after the last non-empty
+/// sibling.
+fn depth(siblings: &[Hash]) -> usize {
+    siblings
+        .iter()
+        .rposition(|s| *s != EMPTY_HASH)
+        .map_or(0, |i| i + 1)
+}
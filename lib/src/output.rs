@@ -0,0 +1,378 @@
+use crate::{
+    biguint_to_hash, biguint_to_value, hash_to_u256, Hash, MerkleProof, MerkleProofSol,
+    PublicValuesStruct, RootKeyPublicValuesStruct, RootPublicValuesStruct,
+    RootValueHashPublicValuesStruct, TreeConfig, TreeConfigSol, TreeHasher, VerifyError,
+};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use alloy_primitives::{keccak256, Bytes, U256};
+use alloy_sol_types::sol_data::Array;
+use alloy_sol_types::{SolType, SolValue};
+use core::fmt;
+use core::str::FromStr;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+/// Which parts of a verified proof are committed as public values, each one encoded with
+/// its own `sol!` struct. Every mode commits the [`TreeConfigSol`] the proof was verified
+/// against along with them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputMode {
+    /// The whole proof, as [`PublicValuesStruct`].
+    #[default]
+    Full,
+    /// The root and the key, hiding the value, as [`RootKeyPublicValuesStruct`].
+    RootKey,
+    /// The root and the keccak256 hash of the value, hiding the key, as
+    /// [`RootValueHashPublicValuesStruct`].
+    ///
+    /// The hash is not salted, so it only hides values that are hard to guess: a vote or
+    /// any value from a small set is found by hashing every candidate. Such values have to
+    /// be stored in the tree along with a random salt of their own, or proven with
+    /// [`OutputMode::RootKey`] or [`OutputMode::Root`] instead.
+    RootValueHash,
+    /// Only the root, as [`RootPublicValuesStruct`].
+    Root,
+}
+
+impl OutputMode {
+    /// ABI-encodes the public values of `proof`, verified against `config`, for this mode.
+    /// The old leaf of a non-inclusion proof is never committed: its value is empty.
+    pub fn encode<H: TreeHasher>(self, config: &TreeConfig<H>, proof: &MerkleProof) -> Vec<u8> {
+        let config = TreeConfigSol::from(config);
+        match self {
+            OutputMode::Full => PublicValuesStruct::new(config, proof).abi_encode(),
+            OutputMode::RootKey => RootKeyPublicValuesStruct::new(config, proof).abi_encode(),
+            OutputMode::RootValueHash => {
+                RootValueHashPublicValuesStruct::new(config, proof).abi_encode()
+            }
+            OutputMode::Root => RootPublicValuesStruct::new(config, proof).abi_encode(),
+        }
+    }
+
+    /// ABI-encodes the public values of every proof in `proofs` as an array of the struct
+    /// of this mode, in the same order. With [`OutputMode::RootKey`] this is the list of
+    /// (root, key) pairs proven.
+    pub fn encode_all<H: TreeHasher>(
+        self,
+        config: &TreeConfig<H>,
+        proofs: &[MerkleProof],
+    ) -> Vec<u8> {
+        let config = TreeConfigSol::from(config);
+        match self {
+            OutputMode::Full => {
+                encode_array(proofs, |p| PublicValuesStruct::new(config.clone(), p))
+            }
+            OutputMode::RootKey => encode_array(proofs, |p| {
+                RootKeyPublicValuesStruct::new(config.clone(), p)
+            }),
+            OutputMode::RootValueHash => encode_array(proofs, |p| {
+                RootValueHashPublicValuesStruct::new(config.clone(), p)
+            }),
+            OutputMode::Root => {
+                encode_array(proofs, |p| RootPublicValuesStruct::new(config.clone(), p))
+            }
+        }
+    }
+
+    /// Checks that `public_values`, as committed by the program in this mode, prove the
+    /// `expected` statements in the same order, in a tree with `config`. Only the parts
+    /// the mode reveals are compared, e.g. the key is not checked with
+    /// [`OutputMode::RootValueHash`], but the config always is: the key length decides
+    /// where the key ends and the value starts in a leaf.
+    ///
+    /// A valid proof only shows that the program ran, so this is what ties it to the
+    /// statement the verifier cares about.
+    pub fn check_all<H: TreeHasher>(
+        self,
+        public_values: &[u8],
+        config: &TreeConfig<H>,
+        expected: &[Statement],
+    ) -> Result<(), PublicValuesError> {
+        let config = TreeConfigSol::from(config);
+        match self {
+            OutputMode::Full => {
+                check_array(public_values, expected, |v: &PublicValuesStruct, s| {
+                    let proof = &v.proof;
+                    v.config == config
+                        && proof.root == hash_to_u256(&s.root)
+                        && proof.key == hash_to_u256(&s.key)
+                        && proof.fnc == s.value.is_none()
+                        && s.value.iter().all(|value| proof.value[..] == value[..])
+                })
+            }
+            OutputMode::RootKey => check_array(
+                public_values,
+                expected,
+                |v: &RootKeyPublicValuesStruct, s| {
+                    v.config == config
+                        && v.root == hash_to_u256(&s.root)
+                        && v.key == hash_to_u256(&s.key)
+                        && v.fnc == s.value.is_none()
+                },
+            ),
+            OutputMode::RootValueHash => check_array(
+                public_values,
+                expected,
+                |v: &RootValueHashPublicValuesStruct, s| {
+                    v.config == config
+                        && v.root == hash_to_u256(&s.root)
+                        && v.fnc == s.value.is_none()
+                        && s.value.iter().all(|value| v.valueHash == keccak256(value))
+                },
+            ),
+            OutputMode::Root => {
+                check_array(public_values, expected, |v: &RootPublicValuesStruct, s| {
+                    v.config == config && v.root == hash_to_u256(&s.root)
+                })
+            }
+        }
+    }
+}
+
+/// What a proof is expected to show about a tree: `key` holds `value`, or isn't in the
+/// tree if `value` is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub root: Hash,
+    pub key: Hash,
+    pub value: Option<Vec<u8>>,
+}
+
+impl Statement {
+    /// Builds a statement from numbers as they appear in arbo's JSON proofs. The value is
+    /// `value_len` bytes long if given, which values ending in zero bytes need, or else
+    /// sized as [`MerkleProofFromFile`](crate::MerkleProofFromFile) does without `valueLen`.
+    pub fn from_biguints(
+        root: &BigUint,
+        key: &BigUint,
+        value: Option<&BigUint>,
+        value_len: Option<usize>,
+        key_len: usize,
+    ) -> Result<Self, VerifyError> {
+        Ok(Statement {
+            root: biguint_to_hash(root)?,
+            key: biguint_to_hash(key)?,
+            value: value
+                .map(|v| biguint_to_value(v, value_len, key_len))
+                .transpose()?,
+        })
+    }
+}
+
+/// The statement of `proof`. The old leaf of a non-inclusion proof is not part of it.
+impl From<&MerkleProof> for Statement {
+    fn from(proof: &MerkleProof) -> Self {
+        Statement {
+            root: proof.root,
+            key: proof.key,
+            value: (!proof.fnc).then(|| proof.value.clone()),
+        }
+    }
+}
+
+/// Errors returned by [`OutputMode::check_all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicValuesError {
+    /// The public values are not an array of the struct of the mode.
+    Decode,
+    /// The public values hold a different number of proofs than statements expected.
+    Count { expected: usize, committed: usize },
+    /// The proof at `index` shows something other than the expected statement.
+    Mismatch { index: usize },
+}
+
+impl fmt::Display for PublicValuesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicValuesError::Decode => {
+                write!(f, "public values don't match the output mode")
+            }
+            PublicValuesError::Count {
+                expected,
+                committed,
+            } => write!(
+                f,
+                "expected {} proofs but the public values commit {}",
+                expected, committed
+            ),
+            PublicValuesError::Mismatch { index } => {
+                write!(f, "proof {} doesn't match the expected statement", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PublicValuesError {}
+
+fn check_array<T>(
+    public_values: &[u8],
+    expected: &[Statement],
+    matches: impl Fn(&T, &Statement) -> bool,
+) -> Result<(), PublicValuesError>
+where
+    T: SolType<RustType = T>,
+{
+    let committed = <Array<T> as SolType>::abi_decode(public_values, true)
+        .map_err(|_| PublicValuesError::Decode)?;
+    if committed.len() != expected.len() {
+        return Err(PublicValuesError::Count {
+            expected: expected.len(),
+            committed: committed.len(),
+        });
+    }
+    match committed
+        .iter()
+        .zip(expected)
+        .position(|(v, s)| !matches(v, s))
+    {
+        Some(index) => Err(PublicValuesError::Mismatch { index }),
+        None => Ok(()),
+    }
+}
+
+fn encode_array<T: SolValue>(proofs: &[MerkleProof], new: impl Fn(&MerkleProof) -> T) -> Vec<u8> {
+    proofs.iter().map(new).collect::<Vec<_>>().abi_encode()
+}
+
+impl FromStr for OutputMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(OutputMode::Full),
+            "root-key" => Ok(OutputMode::RootKey),
+            "root-value-hash" => Ok(OutputMode::RootValueHash),
+            "root" => Ok(OutputMode::Root),
+            _ => Err("expected one of full, root-key, root-value-hash or root"),
+        }
+    }
+}
+
+fn proof_value(proof: &MerkleProof) -> &[u8] {
+    if proof.fnc {
+        &[]
+    } else {
+        &proof.value
+    }
+}
+
+fn proof_kind(fnc: bool) -> &'static str {
+    if fnc {
+        "non-inclusion"
+    } else {
+        "inclusion"
+    }
+}
+
+impl<H: TreeHasher> From<&TreeConfig<H>> for TreeConfigSol {
+    fn from(config: &TreeConfig<H>) -> Self {
+        TreeConfigSol {
+            maxLevels: U256::from(config.max_levels),
+            keyLen: U256::from(config.key_len),
+            hasher: H::NAME.to_string(),
+        }
+    }
+}
+
+impl PublicValuesStruct {
+    /// Commits all of `proof` along with `config`, hiding nothing but the old leaf of a
+    /// non-inclusion proof, whose value is left empty.
+    pub fn new(config: TreeConfigSol, proof: &MerkleProof) -> Self {
+        PublicValuesStruct {
+            config,
+            proof: MerkleProofSol {
+                root: hash_to_u256(&proof.root),
+                key: hash_to_u256(&proof.key),
+                value: Bytes::copy_from_slice(proof_value(proof)),
+                siblings: proof.siblings.iter().map(hash_to_u256).collect(),
+                fnc: proof.fnc,
+            },
+        }
+    }
+}
+
+impl RootKeyPublicValuesStruct {
+    /// Commits the root and key of `proof` and whether the key is in the tree, hiding the
+    /// value and the siblings.
+    pub fn new(config: TreeConfigSol, proof: &MerkleProof) -> Self {
+        RootKeyPublicValuesStruct {
+            config,
+            root: hash_to_u256(&proof.root),
+            key: hash_to_u256(&proof.key),
+            fnc: proof.fnc,
+        }
+    }
+}
+
+impl RootValueHashPublicValuesStruct {
+    /// Commits the root of `proof` and the hash of its value, which is the hash of an empty
+    /// value for a non-inclusion proof, hiding the key and the siblings.
+    pub fn new(config: TreeConfigSol, proof: &MerkleProof) -> Self {
+        RootValueHashPublicValuesStruct {
+            config,
+            root: hash_to_u256(&proof.root),
+            valueHash: keccak256(proof_value(proof)),
+            fnc: proof.fnc,
+        }
+    }
+}
+
+impl RootPublicValuesStruct {
+    /// Commits only the root of `proof`, hiding the key, the value and even whether the key
+    /// is in the tree.
+    pub fn new(config: TreeConfigSol, proof: &MerkleProof) -> Self {
+        RootPublicValuesStruct {
+            config,
+            root: hash_to_u256(&proof.root),
+        }
+    }
+}
+
+impl fmt::Display for TreeConfigSol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "tree: {} levels, {}-byte keys, {}",
+            self.maxLevels, self.keyLen, self.hasher
+        )
+    }
+}
+
+impl fmt::Display for PublicValuesStruct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let proof = &self.proof;
+        write!(f, "{}", self.config)?;
+        writeln!(f, "{} proof", proof_kind(proof.fnc))?;
+        writeln!(f, "root: {}", proof.root)?;
+        writeln!(f, "key: {}", proof.key)?;
+        writeln!(f, "value: {}", proof.value)?;
+        writeln!(f, "siblings: {}", proof.siblings.len())
+    }
+}
+
+impl fmt::Display for RootKeyPublicValuesStruct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.config)?;
+        writeln!(f, "{} proof", proof_kind(self.fnc))?;
+        writeln!(f, "root: {}", self.root)?;
+        writeln!(f, "key: {}", self.key)
+    }
+}
+
+impl fmt::Display for RootValueHashPublicValuesStruct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.config)?;
+        writeln!(f, "{} proof", proof_kind(self.fnc))?;
+        writeln!(f, "root: {}", self.root)?;
+        writeln!(f, "value hash: {}", self.valueHash)
+    }
+}
+
+impl fmt::Display for RootPublicValuesStruct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.config)?;
+        writeln!(f, "root: {}", self.root)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::hasher::{Hash, EMPTY_HASH};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Errors returned when packing or unpacking siblings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// The packed bytes are shorter than the header, or their length doesn't match it.
+    InvalidLength,
+    /// The bitmap length in the header doesn't fit in the packed bytes.
+    InvalidBitmapLength,
+    /// The siblings section is not a multiple of the hash length.
+    InvalidSiblingsLength,
+    /// The packed encoding would not fit in the 2-byte length fields.
+    TooLong,
+    /// There are more siblings than the requested number of levels.
+    TooManySiblings,
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::InvalidLength => write!(f, "packed siblings length mismatch"),
+            PackError::InvalidBitmapLength => write!(f, "packed siblings bitmap length mismatch"),
+            PackError::InvalidSiblingsLength => {
+                write!(f, "packed siblings are not a multiple of the hash length")
+            }
+            PackError::TooLong => write!(f, "too many siblings to pack"),
+            PackError::TooManySiblings => write!(f, "more siblings than levels"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PackError {}
+
+/// Packs the siblings as arbo's `PackSiblings` does:
+///
+/// ```text
+/// [    2 byte   |     2 byte        | L bytes |      S * N bytes    ]
+/// [ full length | bitmap length (L) |  bitmap | N non-zero siblings ]
+/// ```
+///
+/// The bitmap has a bit set for each non-empty sibling, and both lengths are
+/// little-endian.
+pub fn pack_siblings(siblings: &[Hash]) -> Result<Vec<u8>, PackError> {
+    let mut bitmap = vec![0u8; siblings.len().div_ceil(8)];
+    let mut packed = Vec::new();
+    for (i, sibling) in siblings.iter().enumerate() {
+        if *sibling != EMPTY_HASH {
+            bitmap[i / 8] |= 1 << (i % 8);
+            packed.extend_from_slice(sibling);
+        }
+    }
+
+    let full_len = 4 + bitmap.len() + packed.len();
+    if full_len > u16::MAX as usize {
+        return Err(PackError::TooLong);
+    }
+    let mut res = Vec::with_capacity(full_len);
+    res.extend_from_slice(&(full_len as u16).to_le_bytes());
+    res.extend_from_slice(&(bitmap.len() as u16).to_le_bytes());
+    res.extend_from_slice(&bitmap);
+    res.extend_from_slice(&packed);
+    Ok(res)
+}
+
+/// Unpacks siblings packed with [`pack_siblings`] (or arbo's `PackSiblings`).
+///
+/// As in arbo, the result ends at the last non-empty sibling, so callers that need
+/// a fixed number of levels have to pad it with empty siblings.
+pub fn unpack_siblings(b: &[u8]) -> Result<Vec<Hash>, PackError> {
+    let hash_len = EMPTY_HASH.len();
+    if b.len() < 4 {
+        return Err(PackError::InvalidLength);
+    }
+    let full_len = u16::from_le_bytes([b[0], b[1]]) as usize;
+    let bitmap_len = u16::from_le_bytes([b[2], b[3]]) as usize;
+    if b.len() != full_len {
+        return Err(PackError::InvalidLength);
+    }
+    if bitmap_len > full_len - 4 {
+        return Err(PackError::InvalidBitmapLength);
+    }
+    let bitmap = &b[4..4 + bitmap_len];
+    let packed = &b[4 + bitmap_len..];
+    if packed.len() % hash_len != 0 {
+        return Err(PackError::InvalidSiblingsLength);
+    }
+
+    let mut siblings = Vec::new();
+    let mut chunks = packed.chunks_exact(hash_len);
+    for i in 0..bitmap_len * 8 {
+        if chunks.len() == 0 {
+            break;
+        }
+        if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+            siblings.push(chunks.next().unwrap().try_into().unwrap());
+        } else {
+            siblings.push(EMPTY_HASH);
+        }
+    }
+    Ok(siblings)
+}
+
+/// Unpacks `packed` and pads the result with empty siblings up to `levels`.
+pub(crate) fn unpack_padded_siblings(packed: &[u8], levels: usize) -> Result<Vec<Hash>, PackError> {
+    let mut siblings = unpack_siblings(packed)?;
+    if siblings.len() > levels {
+        return Err(PackError::TooManySiblings);
+    }
+    siblings.resize(levels, EMPTY_HASH);
+    Ok(siblings)
+}
+
+/// Serde adapter that stores siblings packed, prefixed by their count, so that
+/// proofs written to the zkVM stdin don't carry every empty sibling.
+pub(crate) mod packed {
+    use super::{pack_siblings, unpack_padded_siblings};
+    use crate::hasher::Hash;
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(siblings: &[Hash], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let levels = u16::try_from(siblings.len()).map_err(serde::ser::Error::custom)?;
+        let packed = pack_siblings(siblings).map_err(serde::ser::Error::custom)?;
+        (levels, packed).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Hash>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (levels, packed): (u16, Vec<u8>) = Deserialize::deserialize(deserializer)?;
+        unpack_padded_siblings(&packed, levels as usize).map_err(serde::de::Error::custom)
+    }
+}
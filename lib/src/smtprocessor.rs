@@ -0,0 +1,190 @@
+use crate::config::TreeConfig;
+use crate::hasher::{Hash, TreeHasher, EMPTY_HASH};
+use crate::smtverifier::{key_bit, leaf_hash, level_ins, switcher, VerifyError};
+use alloc::vec;
+use serde::{Deserialize, Serialize};
+
+/// The operation applied by a state transition, equivalent to circomlib's `fnc[2]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    Nop,
+    Update,
+    Insert,
+    Delete,
+}
+
+impl Operation {
+    /// Returns circomlib's (fnc[0], fnc[1]) pair for this operation.
+    fn fnc(self) -> (bool, bool) {
+        match self {
+            Operation::Nop => (false, false),
+            Operation::Update => (false, true),
+            Operation::Insert => (true, false),
+            Operation::Delete => (true, true),
+        }
+    }
+}
+
+/// Verifies that applying `op` on `new_key` transforms the tree with root `old_root`
+/// into the tree with root `new_root`, as circomlib's SMTProcessor does.
+///
+/// For insert, `siblings`, `old_key`, `old_value` and `is_old_0` are those of the
+/// non-inclusion proof of `new_key` in the old tree. For update they are those of the
+/// inclusion proof, with `old_value` being the previous value. For delete they describe the
+/// insertion of `new_key` into the new tree, i.e. siblings are the ones left after
+/// collapsing, and `old_key`/`old_value` is the leaf that took the deleted leaf's place
+/// (or `is_old_0` if there is none).
+#[allow(clippy::too_many_arguments)]
+pub fn verify_transition<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    old_root: &Hash,
+    new_root: &Hash,
+    siblings: &[Hash],
+    old_key: &Hash,
+    old_value: &[u8],
+    is_old_0: bool,
+    new_key: &Hash,
+    new_value: &[u8],
+    op: Operation,
+) -> Result<(), VerifyError> {
+    let (fnc0, fnc1) = op.fnc();
+    let enabled = fnc0 || fnc1;
+    if !enabled {
+        if old_root != new_root {
+            return Err(VerifyError::RootMismatch {
+                expected: *new_root,
+                computed: *old_root,
+            });
+        }
+        return Ok(());
+    }
+
+    // update requires both keys to be equal
+    if !fnc0 && fnc1 && old_key != new_key {
+        return Err(VerifyError::KeyMismatch);
+    }
+
+    let siblings = config.check_proof(siblings, &[old_key, new_key])?;
+    let hasher = &config.hasher;
+
+    // the state machine runs one level further than the siblings, which is always empty
+    let n_levels = siblings.len() + 1;
+    let lev_ins = level_ins(&siblings);
+
+    let mut states = vec![ProcessorState::default(); n_levels];
+    for i in 0..n_levels {
+        let prev = if i == 0 {
+            ProcessorState {
+                top: enabled,
+                na: !enabled,
+                ..Default::default()
+            }
+        } else {
+            states[i - 1]
+        };
+        let xor = key_bit(old_key, i) != key_bit(new_key, i);
+        states[i] = sm_processor(is_old_0, xor, lev_ins[i], fnc0, prev);
+    }
+
+    let last = states[n_levels - 1];
+    if last.na as u8 + last.new1 as u8 + last.old0 as u8 + last.upd as u8 != 1 {
+        return Err(VerifyError::InvalidStateMachine);
+    }
+
+    // only the leaves the state machine goes through are hashed: the old one is empty
+    // when inserting next to an empty node
+    let hash1_old = if states.iter().any(|st| st.bot || st.new1 || st.upd) {
+        leaf_hash(hasher, old_key, old_value, config.key_len)?
+    } else {
+        EMPTY_HASH
+    };
+    let hash1_new = if states.iter().any(|st| st.new1 || st.old0 || st.upd) {
+        leaf_hash(hasher, new_key, new_value, config.key_len)?
+    } else {
+        EMPTY_HASH
+    };
+
+    let mut old_child = EMPTY_HASH;
+    let mut new_child = EMPTY_HASH;
+    for i in (0..n_levels).rev() {
+        let st = states[i];
+        let sibling = siblings.get(i).unwrap_or(&EMPTY_HASH);
+        let lrbit = key_bit(new_key, i) as u8;
+
+        let old_level = if st.top {
+            let (l, r) = switcher(lrbit, &old_child, sibling);
+            hasher.node_hash(l, r)?
+        } else if st.bot || st.new1 || st.upd {
+            hash1_old
+        } else {
+            EMPTY_HASH
+        };
+
+        let new_level = if st.top {
+            let (l, r) = switcher(lrbit, &new_child, sibling);
+            hasher.node_hash(l, r)?
+        } else if st.bot {
+            let (l, r) = switcher(lrbit, &new_child, &EMPTY_HASH);
+            hasher.node_hash(l, r)?
+        } else if st.new1 {
+            let (l, r) = switcher(lrbit, &hash1_new, &hash1_old);
+            hasher.node_hash(l, r)?
+        } else if st.old0 || st.upd {
+            hash1_new
+        } else {
+            EMPTY_HASH
+        };
+
+        old_child = old_level;
+        new_child = new_level;
+    }
+
+    // a delete is an insert run backwards
+    let (computed_old, computed_new) = if fnc0 && fnc1 {
+        (new_child, old_child)
+    } else {
+        (old_child, new_child)
+    };
+
+    for (expected, computed) in [(old_root, computed_old), (new_root, computed_new)] {
+        if *expected != computed {
+            return Err(VerifyError::RootMismatch {
+                expected: *expected,
+                computed,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ProcessorState {
+    top: bool,
+    old0: bool,
+    bot: bool,
+    new1: bool,
+    na: bool,
+    upd: bool,
+}
+
+fn sm_processor(
+    is_0: bool,
+    xor: bool,
+    lev_ins: bool,
+    fnc0: bool,
+    prev: ProcessorState,
+) -> ProcessorState {
+    let aux1 = prev.top && lev_ins;
+    let aux2 = aux1 && fnc0;
+    let old0 = aux2 && is_0;
+    let pushing = (aux2 && !old0) || prev.bot;
+    ProcessorState {
+        top: prev.top && !aux1,
+        old0,
+        bot: pushing && !xor,
+        new1: pushing && xor,
+        na: prev.new1 || prev.old0 || prev.na || prev.upd,
+        upd: aux1 && !aux2,
+    }
+}
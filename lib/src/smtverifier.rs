@@ -1,71 +1,265 @@
-use blake3;
-use num_bigint::BigUint;
-use num_traits::{One, Zero};
-
-fn siblings_biguints_to_bytes(siblings: Vec<BigUint>) -> Vec<Vec<u8>> {
-    let hash_len = 32; // for BLAKE3
-
-    println!(
-        "got {} siblings, using {} bytes as hash_len",
-        siblings.len(),
-        hash_len
-    ); // debug
-
-    let to_bytes = |i: &BigUint| -> Vec<u8> {
-        let mut b = i.to_bytes_le();
-        b.resize(hash_len, 0u8); // pad with zeroes
-        b
-    };
-
-    let mut siblings: Vec<Vec<u8>> = siblings
-        .into_iter()
-        .map(|biguint| to_bytes(&biguint))
-        .collect();
-
-    for sibling in siblings.iter_mut() {
-        // if the sibling is empty or zero, pad with zeroes
-        if sibling.is_empty() || sibling.iter().all(|&byte| byte == 0) {
-            *sibling = vec![0u8; hash_len];
+use crate::config::TreeConfig;
+use crate::hasher::{Hash, TreeHasher, EMPTY_HASH};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Reasons why a proof can be rejected by the verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The root computed from the proof does not match the expected root.
+    RootMismatch { expected: Hash, computed: Hash },
+    /// The state machine did not end in exactly one terminal state.
+    InvalidStateMachine,
+    /// The tree configuration has no levels, more than a 32-byte key can address, or a
+    /// key length that is zero or longer than 32 bytes.
+    InvalidConfig { max_levels: usize, key_len: usize },
+    /// The proof has more siblings than the tree has levels.
+    TooManySiblings { siblings: usize, max_levels: usize },
+    /// A non-inclusion proof was given with an old leaf that has the same key.
+    FncOldKeyConflict,
+    /// The key (or old key) does not fit in the key length of the tree.
+    KeyTooWide { key_bits: u64, max_bits: u64 },
+    /// An update transition was given with different old and new keys.
+    KeyMismatch,
+    /// A key, value or sibling is not a valid input for the hash function.
+    InvalidHashInput,
+    /// A proof batch is empty, has mismatched or repeated keys, or mixes roots.
+    InvalidBatch,
+    /// The trees in a proof batch differ outside of the batch keys.
+    UnexpectedChange,
+    /// A multiproof is empty, has repeated keys, keys that disagree on the node where
+    /// their paths end, or more or fewer nodes than its paths need.
+    InvalidMultiProof,
+    /// A root, key or sibling does not fit in 32 bytes, or a value doesn't fit in its
+    /// explicit length.
+    Overflow,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::RootMismatch { expected, computed } => write!(
+                f,
+                "root mismatch: expected {}, computed {}",
+                hex::encode(expected),
+                hex::encode(computed)
+            ),
+            VerifyError::InvalidStateMachine => {
+                write!(f, "state machine did not terminate in a valid state")
+            }
+            VerifyError::InvalidConfig {
+                max_levels,
+                key_len,
+            } => write!(
+                f,
+                "invalid tree config: {} levels with {}-byte keys",
+                max_levels, key_len
+            ),
+            VerifyError::TooManySiblings {
+                siblings,
+                max_levels,
+            } => write!(
+                f,
+                "proof has {} siblings but the tree only has {} levels",
+                siblings, max_levels
+            ),
+            VerifyError::FncOldKeyConflict => {
+                write!(f, "non-inclusion proof with old key equal to key")
+            }
+            VerifyError::KeyTooWide { key_bits, max_bits } => write!(
+                f,
+                "key is {} bits wide but the tree only has {} bits",
+                key_bits, max_bits
+            ),
+            VerifyError::KeyMismatch => write!(f, "update with old key different from new key"),
+            VerifyError::InvalidHashInput => {
+                write!(f, "input is not valid for the hash function")
+            }
+            VerifyError::InvalidBatch => write!(f, "invalid proof batch"),
+            VerifyError::UnexpectedChange => {
+                write!(f, "trees differ outside of the batch keys")
+            }
+            VerifyError::InvalidMultiProof => write!(f, "invalid multiproof"),
+            VerifyError::Overflow => write!(
+                f,
+                "root, key or sibling does not fit in 32 bytes, or value does not fit in its length"
+            ),
         }
     }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
+
+/// The steps taken by the verifier to compute a root, as returned by [`trace_extended`].
+///
+/// Hashes are little-endian and serialized as hex strings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerificationTrace {
+    /// One entry per level, from the root (level 0) down to the extra empty level.
+    pub levels: Vec<LevelTrace>,
+    #[serde(with = "hex::serde")]
+    pub root: Hash,
+}
 
-    siblings
+/// The inputs, output and state of the verifier state machine at one level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelTrace {
+    /// Key bit at this level, true if the path goes right.
+    pub lrbit: bool,
+    /// Hash computed for the level below.
+    #[serde(with = "hex::serde")]
+    pub child: Hash,
+    #[serde(with = "hex::serde")]
+    pub sibling: Hash,
+    /// Hash computed for this level.
+    #[serde(with = "hex::serde")]
+    pub hash: Hash,
+    pub top: bool,
+    pub inew: bool,
+    pub iold: bool,
+    pub i0: bool,
+    pub na: bool,
 }
 
-pub(crate) fn verify_extended(
+impl fmt::Display for VerificationTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, level) in self.levels.iter().enumerate() {
+            if level.top {
+                writeln!(
+                    f,
+                    "level {} {} {} + {} = {}",
+                    i,
+                    level.lrbit as u8,
+                    pretty_hash(&level.child),
+                    pretty_hash(&level.sibling),
+                    pretty_hash(&level.hash),
+                )?;
+            } else if level.inew {
+                writeln!(f, "level {} new = {}", i, pretty_hash(&level.hash))?;
+            } else if level.iold {
+                writeln!(f, "level {} old = {}", i, pretty_hash(&level.hash))?;
+            } else if level.i0 {
+                writeln!(f, "level {} empty", i)?;
+            }
+        }
+        writeln!(f, "root: {}", hex::encode(self.root))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn verify_extended<H: TreeHasher>(
+    config: &TreeConfig<H>,
     enabled: bool,
-    expected_root: &BigUint,
-    old_key: &BigUint,
-    old_value: &BigUint,
+    expected_root: &Hash,
+    old_key: &Hash,
+    old_value: &[u8],
     is_old_0: bool,
-    key: &BigUint,
-    value: &BigUint,
+    key: &Hash,
+    value: &[u8],
     fnc: bool,
-    siblings_biguint: Vec<BigUint>,
-) {
-    let mut siblings = siblings_biguints_to_bytes(siblings_biguint);
-    let required_len = ((siblings.len() - 1) + 7) / 8; // Calculate the ceil value of (n_levels-1)/8
+    siblings: &[Hash],
+) -> Result<(), VerifyError> {
+    let computed_root = compute_root_extended(
+        config, enabled, old_key, old_value, is_old_0, key, value, fnc, siblings,
+    )?;
+
+    if *expected_root != computed_root {
+        return Err(VerifyError::RootMismatch {
+            expected: *expected_root,
+            computed: computed_root,
+        });
+    }
 
-    let to_bytes = |i: &BigUint| -> Vec<u8> {
-        let mut b = i.to_bytes_le();
-        b.resize(required_len, 0u8);
-        b
-    };
+    if fnc && !is_old_0 && old_key == key && enabled {
+        return Err(VerifyError::FncOldKeyConflict);
+    }
 
-    // Ensure the last sibling is zero
-    siblings.push(vec![0u8; 32]);
+    Ok(())
+}
 
-    let n_levels = siblings.len();
-    let hash1_old = end_leaf_hash(&to_bytes(old_key), &to_bytes(old_value));
-    let hash1_new = end_leaf_hash(&to_bytes(key), &to_bytes(value));
+/// Computes the root implied by a proof, without comparing it to any expected root.
+///
+/// For inclusion proofs (`fnc == false`) this is the root of a tree holding `key` and
+/// `value`, for non-inclusion proofs the root of the tree holding the old leaf (or an
+/// empty node if `is_old_0`) where `key` would be. The root is returned little-endian,
+/// as arbo stores it.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_root_extended<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    enabled: bool,
+    old_key: &Hash,
+    old_value: &[u8],
+    is_old_0: bool,
+    key: &Hash,
+    value: &[u8],
+    fnc: bool,
+    siblings: &[Hash],
+) -> Result<Hash, VerifyError> {
+    compute_root_traced(
+        config, enabled, old_key, old_value, is_old_0, key, value, fnc, siblings, None,
+    )
+}
+
+/// Same as [`compute_root_extended`], but returns every step taken to compute the root.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_extended<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    enabled: bool,
+    old_key: &Hash,
+    old_value: &[u8],
+    is_old_0: bool,
+    key: &Hash,
+    value: &[u8],
+    fnc: bool,
+    siblings: &[Hash],
+) -> Result<VerificationTrace, VerifyError> {
+    let mut trace = VerificationTrace::default();
+    trace.root = compute_root_traced(
+        config,
+        enabled,
+        old_key,
+        old_value,
+        is_old_0,
+        key,
+        value,
+        fnc,
+        siblings,
+        Some(&mut trace.levels),
+    )?;
+    Ok(trace)
+}
 
-    let lev_ins = level_ins(&siblings, enabled);
+#[allow(clippy::too_many_arguments)]
+fn compute_root_traced<H: TreeHasher>(
+    config: &TreeConfig<H>,
+    enabled: bool,
+    old_key: &Hash,
+    old_value: &[u8],
+    is_old_0: bool,
+    key: &Hash,
+    value: &[u8],
+    fnc: bool,
+    siblings: &[Hash],
+    mut trace: Option<&mut Vec<LevelTrace>>,
+) -> Result<Hash, VerifyError> {
+    let siblings = config.check_proof(siblings, &[key, old_key])?;
+    let hasher = &config.hasher;
+
+    // the state machine runs one level further than the siblings, which is always empty
+    let n_levels = siblings.len() + 1;
 
-    let mut st_tops = vec![false; siblings.len()];
-    let mut st_iolds = vec![false; siblings.len()];
-    let mut st_i0s = vec![false; siblings.len()];
-    let mut st_inews = vec![false; siblings.len()];
-    let mut st_nas = vec![false; siblings.len()];
+    let lev_ins = level_ins(&siblings);
+
+    let mut st_tops = vec![false; n_levels];
+    let mut st_iolds = vec![false; n_levels];
+    let mut st_i0s = vec![false; n_levels];
+    let mut st_inews = vec![false; n_levels];
+    let mut st_nas = vec![false; n_levels];
 
     for i in 0..n_levels {
         let (st_top, st_inew, st_iold, st_i0, st_na) = if i == 0 {
@@ -91,104 +285,120 @@ pub(crate) fn verify_extended(
         st_nas[i] = st_na;
     }
 
-    assert!(
-        st_nas[n_levels - 1] as u8
-            + st_iolds[n_levels - 1] as u8
-            + st_inews[n_levels - 1] as u8
-            + st_i0s[n_levels - 1] as u8
-            == 1
-    );
-
-    let mut levels = vec![Vec::new(); siblings.len()];
-    let mut i = n_levels - 1;
-    for n in 0..n_levels {
-        let child = if n != 0 {
-            levels[i + 1].clone()
-        } else {
-            BigUint::zero().to_bytes_le()
-        };
-        let lrbit = if key.bit(i.try_into().unwrap()) {
-            1u8
-        } else {
-            0u8
-        };
+    if st_nas[n_levels - 1] as u8
+        + st_iolds[n_levels - 1] as u8
+        + st_inews[n_levels - 1] as u8
+        + st_i0s[n_levels - 1] as u8
+        != 1
+    {
+        return Err(VerifyError::InvalidStateMachine);
+    }
+
+    let mut child = EMPTY_HASH;
+    for i in (0..n_levels).rev() {
+        let sibling = siblings.get(i).unwrap_or(&EMPTY_HASH);
+        let lrbit = key_bit(key, i) as u8;
 
-        levels[i] = if st_tops[i] {
-            let (l, r) = switcher(lrbit, &child, &siblings[i]);
-            let hash = intermediate_leaf_hash(l, r);
-            println!(
-                "level_verifier {} {} + {} = {}",
-                lrbit,
-                pretty_hash(&child),
-                pretty_hash(&siblings[i]),
-                pretty_hash(&hash.clone()),
-            ); // debug
-            hash
+        let hash = if st_tops[i] {
+            let (l, r) = switcher(lrbit, &child, sibling);
+            hasher.node_hash(l, r)?
         } else if st_inews[i] {
-            println!("level_verifier new = {}", pretty_hash(&hash1_new.clone()),); // debug
-            hash1_new.clone()
+            // only the leaf the state machine ends in is hashed: the other one may be
+            // empty, such as the old leaf of an inclusion proof
+            leaf_hash(hasher, key, value, config.key_len)?
         } else if st_iolds[i] {
-            println!("level_verifier old = {}", pretty_hash(&hash1_old.clone()),); // debug
-            hash1_old.clone()
+            leaf_hash(hasher, old_key, old_value, config.key_len)?
         } else {
-            Vec::new()
+            // empty (isOld0) or below the leaf: an empty node hashes as all zeroes
+            EMPTY_HASH
         };
 
-        if i > 0 {
-            i -= 1;
+        if let Some(trace) = trace.as_mut() {
+            trace.push(LevelTrace {
+                lrbit: lrbit == 1,
+                child,
+                sibling: *sibling,
+                hash,
+                top: st_tops[i],
+                inew: st_inews[i],
+                iold: st_iolds[i],
+                i0: st_i0s[i],
+                na: st_nas[i],
+            });
         }
+
+        child = hash;
+    }
+
+    if let Some(trace) = trace {
+        // levels are computed from the bottom up
+        trace.reverse();
     }
 
-    println!(
-        "Expected root: {} (base10: {:?})",
-        hex::encode(expected_root.to_bytes_le()),
-        expected_root
-    );
-    println!(
-        "Computed root: {} (base10: {})",
-        (hex::encode(levels[0].clone())),
-        (BigUint::from_bytes_le(&levels[0]).to_string())
-    );
+    Ok(child)
+}
 
-    assert!(expected_root.to_bytes_le() == levels[0]);
+/// Hashes a leaf as arbo does, using the first `key_len` bytes of its key and the value
+/// bytes as they are, whatever their length.
+pub(crate) fn leaf_hash<H: TreeHasher>(
+    hasher: &H,
+    key: &Hash,
+    value: &[u8],
+    key_len: usize,
+) -> Result<Hash, VerifyError> {
+    hasher.leaf_hash(&key[..key_len], value)
+}
 
-    assert!((fnc && (!is_old_0) && (old_key == key) && enabled) == false);
+/// Returns the bit of `key` that selects the child at level `lvl` (true: right).
+pub(crate) fn key_bit(key: &[u8], lvl: usize) -> bool {
+    key.get(lvl / 8).is_some_and(|b| (b >> (lvl % 8)) & 1 == 1)
 }
 
-fn level_ins(siblings: &Vec<Vec<u8>>, enabled: bool) -> Vec<bool> {
-    // println!(
-    //     "level_ins {:?} {}",
-    //     siblings
-    //         .iter()
-    //         .map(|x| pretty_hash(x))
-    //         .collect::<Vec<String>>(),
-    //     enabled
-    // ); // debug
-    let mut lev_ins = vec![false; siblings.len()];
-    if enabled {
-        assert!(*siblings[siblings.len() - 1] == vec![0u8; 32]);
+/// Number of bits needed to represent the little-endian integer `bytes`.
+fn bit_len(bytes: &[u8]) -> u64 {
+    match bytes.iter().rposition(|&b| b != 0) {
+        Some(i) => (i * 8) as u64 + (8 - bytes[i].leading_zeros()) as u64,
+        None => 0,
     }
+}
+
+pub(crate) fn check_key_width(keys: &[&Hash], key_len: usize) -> Result<(), VerifyError> {
+    let max_bits = (key_len * 8) as u64;
+    for k in keys {
+        let key_bits = bit_len(&k[..]);
+        if key_bits > max_bits {
+            return Err(VerifyError::KeyTooWide { key_bits, max_bits });
+        }
+    }
+    Ok(())
+}
 
-    let is_zero: Vec<bool> = siblings.iter().map(|i| **i == vec![0u8; 32]).collect();
-    // println!("is_zero: {:?}", is_zero); // debug
+/// Returns the levIns flags of circomlib's SMTLevIns for `siblings` followed by the
+/// extra empty level: the level where the leaf sits, right after the last non-empty
+/// sibling.
+pub(crate) fn level_ins(siblings: &[Hash]) -> Vec<bool> {
+    let n_levels = siblings.len() + 1;
+    let mut lev_ins = vec![false; n_levels];
 
-    let mut is_done = vec![false; siblings.len()];
+    let is_zero: Vec<bool> = siblings.iter().map(|s| *s == EMPTY_HASH).collect();
 
-    let last = !is_zero[siblings.len() - 2];
-    lev_ins[siblings.len() - 1] = last;
-    is_done[siblings.len() - 2] = last;
+    let mut is_done = vec![false; n_levels];
 
-    for n in 2..siblings.len() {
-        let i = siblings.len() - n;
+    let last = !is_zero[n_levels - 2];
+    lev_ins[n_levels - 1] = last;
+    is_done[n_levels - 2] = last;
+
+    for n in 2..n_levels {
+        let i = n_levels - n;
         lev_ins[i] = !is_done[i] && !is_zero[i - 1];
         is_done[i - 1] = lev_ins[i] || is_done[i];
     }
     lev_ins[0] = !is_done[0];
-    // println!("lev_ins {:?}", lev_ins); // debug
 
     lev_ins
 }
 
+#[allow(clippy::too_many_arguments)]
 fn sm_verifier(
     is_0: bool,
     lev_ins: bool,
@@ -206,14 +416,10 @@ fn sm_verifier(
     let st_iold = prev_top_lev_ins_fnc && !is_0;
     let st_i0 = prev_top_lev_ins && is_0;
     let st_na = prev_na || prev_inew || prev_iold || prev_i0;
-    // println!(
-    //     "sm_verifier gave: {} {} {} {} {}",
-    //     st_top, st_inew, st_iold, st_i0, st_na
-    // );
     (st_top, st_inew, st_iold, st_i0, st_na)
 }
 
-fn pretty_hash(bytes: &Vec<u8>) -> String {
+fn pretty_hash(bytes: &[u8]) -> String {
     if bytes.len() < 6 {
         return hex::encode(bytes);
     }
@@ -224,43 +430,11 @@ fn pretty_hash(bytes: &Vec<u8>) -> String {
         hex::encode(&bytes[bytes.len() - 3..])
     )
 }
-fn switcher<'a>(lrbit: u8, l: &'a Vec<u8>, r: &'a Vec<u8>) -> (&'a Vec<u8>, &'a Vec<u8>) {
+
+pub(crate) fn switcher<'a>(lrbit: u8, l: &'a Hash, r: &'a Hash) -> (&'a Hash, &'a Hash) {
     if lrbit == 0 {
         (l, r)
     } else {
         (r, l)
     }
 }
-
-// intermediate_leaf_value using Blake3 hash
-pub(crate) fn end_leaf_hash(k: &Vec<u8>, v: &Vec<u8>) -> Vec<u8> {
-    blake3_hash(&[k, v, &BigUint::one().to_bytes_le()])
-}
-
-// intermediate_leaf_value using Blake3 hash
-pub(crate) fn intermediate_leaf_hash(l: &Vec<u8>, r: &Vec<u8>) -> Vec<u8> {
-    blake3_hash(&[l, r])
-}
-
-// fn blake3_hash_from_le_biguints(inputs: &[&BigUint]) -> BigUint {
-//     // circomlib uses little-endian when converting bigints to bytes
-//     let hash = blake3_hash(&inputs.iter().map(|x| x.to_bytes_le()).collect::<Vec<_>>());
-//     BigUint::from_bytes_le(&hash)
-// }
-
-fn blake3_hash(inputs: &[&Vec<u8>]) -> Vec<u8> {
-    let mut hasher = blake3::Hasher::new();
-
-    // Iterate over each input byte slice and pass it to the hasher
-    for input in inputs {
-        // println!("input (hex): {}", hex::encode(input)); // debug
-        hasher.update(input); // Pass the byte slice directly to the hasher
-    }
-
-    // Finalize the hash and return the resulting hash as a Vec<u8>
-    let hash = hasher.finalize();
-
-    // println!("hash (hex): {:?}", hash.to_hex()); // debug
-
-    hash.as_bytes().to_vec()
-}
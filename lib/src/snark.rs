@@ -0,0 +1,105 @@
+use core::fmt;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use sp1_verifier::{Groth16Error, Groth16Verifier, PlonkError, PlonkVerifier};
+
+/// The SNARK an SP1 proof is wrapped in to be verified on-chain, and here without the
+/// prover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofSystem {
+    Groth16,
+    Plonk,
+}
+
+impl ProofSystem {
+    /// The verifying key of the SP1 circuit for this proof system, as shipped with
+    /// `sp1-verifier`. Proofs made with an SP1 version that has other circuits need the
+    /// key of those.
+    pub fn circuit_vk(self) -> &'static [u8] {
+        match self {
+            ProofSystem::Groth16 => *sp1_verifier::GROTH16_VK_BYTES,
+            ProofSystem::Plonk => *sp1_verifier::PLONK_VK_BYTES,
+        }
+    }
+}
+
+impl FromStr for ProofSystem {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "groth16" => Ok(ProofSystem::Groth16),
+            "plonk" => Ok(ProofSystem::Plonk),
+            _ => Err("expected groth16 or plonk"),
+        }
+    }
+}
+
+/// Errors returned by [`verify_snark`].
+#[derive(Debug)]
+pub enum SnarkError {
+    /// The proof is too short for the circuit, or the vkey hash is not `0x` followed by
+    /// hex. `sp1-verifier` panics on those instead of returning an error.
+    Malformed,
+    Groth16(Groth16Error),
+    Plonk(PlonkError),
+}
+
+impl fmt::Display for SnarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnarkError::Malformed => write!(f, "proof or vkey hash is malformed"),
+            SnarkError::Groth16(e) => write!(f, "invalid groth16 proof: {}", e),
+            SnarkError::Plonk(e) => write!(f, "invalid plonk proof: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnarkError {}
+
+/// Verifies a groth16 or plonk SP1 proof of the program with verifying key hash
+/// `vkey_hash` (the `bytes32` of the key, as a hex string), committing `public_values`.
+///
+/// `proof` holds the bytes sent on-chain, i.e. `SP1ProofWithPublicValues::bytes`, and
+/// `circuit_vk` is usually [`ProofSystem::circuit_vk`]. Unlike the SDK, this doesn't need
+/// the program nor the prover, so it also works in `no_std` environments.
+///
+/// The proof and the public values may come from anyone, but `circuit_vk` is trusted: a
+/// malformed one can still make `sp1-verifier` panic.
+pub fn verify_snark(
+    system: ProofSystem,
+    proof: &[u8],
+    public_values: &[u8],
+    vkey_hash: &str,
+    circuit_vk: &[u8],
+) -> Result<(), SnarkError> {
+    let min_len = match system {
+        ProofSystem::Groth16 => Some(GROTH16_PROOF_LEN),
+        ProofSystem::Plonk => plonk_proof_len(circuit_vk),
+    };
+    if min_len.map_or(true, |len| proof.len() < len) || !vkey_hash.starts_with("0x") {
+        return Err(SnarkError::Malformed);
+    }
+
+    match system {
+        ProofSystem::Groth16 => {
+            Groth16Verifier::verify(proof, public_values, vkey_hash, circuit_vk)
+                .map_err(SnarkError::Groth16)
+        }
+        ProofSystem::Plonk => PlonkVerifier::verify(proof, public_values, vkey_hash, circuit_vk)
+            .map_err(SnarkError::Plonk),
+    }
+}
+
+/// The 4-byte prefix of the circuit vk hash, then two G1 points and a G2 one.
+const GROTH16_PROOF_LEN: usize = 4 + 256;
+
+/// The 4-byte prefix of the circuit vk hash, then the fixed part of a gnark plonk proof,
+/// with an extra claimed value and G1 point for each custom gate commitment of the circuit.
+fn plonk_proof_len(circuit_vk: &[u8]) -> Option<usize> {
+    let commitments = u32::from_be_bytes(circuit_vk.get(368..372)?.try_into().ok()?);
+    (commitments as usize)
+        .checked_mul(32 + 64)?
+        .checked_add(4 + 768)
+}
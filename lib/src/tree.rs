@@ -0,0 +1,397 @@
+use crate::config::TreeConfig;
+use crate::hasher::{Blake3, Hash, TreeHasher, EMPTY_HASH};
+use crate::smtprocessor::Operation;
+use crate::smtverifier::key_bit as path_bit;
+use crate::{MerkleProof, TransitionProof};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Errors returned by [`Tree`] operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    /// The key has more bytes than the tree levels allow.
+    KeyTooLong { key_len: usize, max_len: usize },
+    /// The key is already in the tree.
+    KeyAlreadyExists,
+    /// The key is not in the tree.
+    KeyNotFound,
+    /// Two keys share a path longer than the tree levels.
+    MaxLevelsReached,
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::KeyTooLong { key_len, max_len } => write!(
+                f,
+                "key is {} bytes long but the tree only allows {} bytes",
+                key_len, max_len
+            ),
+            TreeError::KeyAlreadyExists => write!(f, "key already exists"),
+            TreeError::KeyNotFound => write!(f, "key not found"),
+            TreeError::MaxLevelsReached => write!(f, "max levels reached"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TreeError {}
+
+/// Blake3 takes inputs of any length, so hashing a node of the tree never fails.
+const BLAKE3_INFALLIBLE: &str = "blake3 hashes any input";
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Leaf {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        hash: Hash,
+    },
+    Intermediate {
+        left: Box<Node>,
+        right: Box<Node>,
+        hash: Hash,
+    },
+}
+
+impl Node {
+    fn new_leaf(key: &[u8], value: &[u8]) -> Node {
+        Node::Leaf {
+            key: key.to_vec(),
+            value: value.to_vec(),
+            hash: Blake3.leaf_hash(key, value).expect(BLAKE3_INFALLIBLE),
+        }
+    }
+
+    fn new_intermediate(left: Node, right: Node) -> Node {
+        Node::Intermediate {
+            hash: Blake3
+                .node_hash(&left.hash(), &right.hash())
+                .expect(BLAKE3_INFALLIBLE),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn hash(&self) -> Hash {
+        match self {
+            Node::Empty => EMPTY_HASH,
+            Node::Leaf { hash, .. } | Node::Intermediate { hash, .. } => *hash,
+        }
+    }
+}
+
+/// An in-memory sparse Merkle tree compatible with vocdoni's arbo using Blake3.
+///
+/// Keys are little-endian and their bits select the path from the root, so the roots
+/// and proofs match the ones produced by arbo for the same set of leaves. As in arbo,
+/// the leaf hash uses the key bytes as given, so keys should always be passed with the
+/// same length (usually [`Tree::key_len`]).
+#[derive(Debug, Clone)]
+pub struct Tree {
+    max_levels: usize,
+    key_len: usize,
+    root: Node,
+}
+
+impl Tree {
+    pub fn new(max_levels: usize) -> Self {
+        Tree::with_config(TreeConfig::new(max_levels, Blake3))
+    }
+
+    /// Returns an empty tree of `config`, whose keys may be longer than the levels need.
+    pub fn with_config(config: TreeConfig) -> Self {
+        Tree {
+            max_levels: config.max_levels,
+            key_len: config.key_len,
+            root: Node::Empty,
+        }
+    }
+
+    pub fn max_levels(&self) -> usize {
+        self.max_levels
+    }
+
+    /// Length of the keys, by default the number of bytes needed to address every level.
+    pub fn key_len(&self) -> usize {
+        self.key_len
+    }
+
+    /// The configuration to verify the proofs of this tree with.
+    pub fn config(&self) -> TreeConfig {
+        TreeConfig {
+            max_levels: self.max_levels,
+            key_len: self.key_len,
+            hasher: Blake3,
+        }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.root.hash()
+    }
+
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<(), TreeError> {
+        self.check_key(key)?;
+        add_at(&mut self.root, key, value, 0, self.max_levels)
+    }
+
+    pub fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), TreeError> {
+        self.check_key(key)?;
+        update_at(&mut self.root, key, value, 0)
+    }
+
+    /// Removes `key` from the tree, collapsing the remaining leaf upwards when it is
+    /// left without siblings, so the resulting root is the same as if `key` was never added.
+    pub fn delete(&mut self, key: &[u8]) -> Result<(), TreeError> {
+        self.check_key(key)?;
+        delete_at(&mut self.root, key, 0)
+    }
+
+    /// Adds `key` like [`Tree::add`], returning the proof of the transition.
+    pub fn add_with_proof(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<TransitionProof, TreeError> {
+        let proof = self.generate_circom_verifier_proof(key)?;
+        self.add(key, value)?;
+        Ok(TransitionProof {
+            old_root: proof.root,
+            new_root: self.root(),
+            siblings: proof.siblings,
+            old_key: proof.old_key,
+            old_value: proof.old_value,
+            is_old_0: proof.is_old_0,
+            new_key: proof.key,
+            new_value: value.to_vec(),
+            op: Operation::Insert,
+        })
+    }
+
+    /// Updates `key` like [`Tree::update`], returning the proof of the transition.
+    pub fn update_with_proof(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<TransitionProof, TreeError> {
+        let proof = self.generate_circom_verifier_proof(key)?;
+        self.update(key, value)?;
+        Ok(TransitionProof {
+            old_root: proof.root,
+            new_root: self.root(),
+            siblings: proof.siblings,
+            old_key: proof.key,
+            old_value: proof.value,
+            is_old_0: false,
+            new_key: proof.key,
+            new_value: value.to_vec(),
+            op: Operation::Update,
+        })
+    }
+
+    /// Deletes `key` like [`Tree::delete`], returning the proof of the transition, which
+    /// is the one of inserting `key` back.
+    pub fn delete_with_proof(&mut self, key: &[u8]) -> Result<TransitionProof, TreeError> {
+        let old_root = self.root();
+        let value = self.get(key)?;
+        self.delete(key)?;
+        let proof = self.generate_circom_verifier_proof(key)?;
+        Ok(TransitionProof {
+            old_root,
+            new_root: proof.root,
+            siblings: proof.siblings,
+            old_key: proof.old_key,
+            old_value: proof.old_value,
+            is_old_0: proof.is_old_0,
+            new_key: proof.key,
+            new_value: value,
+            op: Operation::Delete,
+        })
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Vec<u8>, TreeError> {
+        self.check_key(key)?;
+        match self.down(key).1 {
+            Node::Leaf { key: k, value, .. } if k == key => Ok(value.clone()),
+            _ => Err(TreeError::KeyNotFound),
+        }
+    }
+
+    /// Generates an inclusion proof for `key`, or a non-inclusion proof if `key` is not
+    /// in the tree, with the same layout as arbo's `GenerateCircomVerifierProof`.
+    ///
+    /// Unlike arbo, a non-inclusion proof that ends in an empty node sets `is_old_0`,
+    /// as the circom verifier requires.
+    pub fn generate_circom_verifier_proof(&self, key: &[u8]) -> Result<MerkleProof, TreeError> {
+        self.check_key(key)?;
+        let (mut siblings, node) = self.down(key);
+        siblings.resize(self.max_levels, EMPTY_HASH);
+
+        let mut proof = MerkleProof {
+            root: self.root(),
+            key: to_hash(key).ok_or(TreeError::KeyTooLong {
+                key_len: key.len(),
+                max_len: EMPTY_HASH.len(),
+            })?,
+            // zero, as long as the key, as arbo encodes unused values
+            value: vec![0; key.len()],
+            siblings,
+            fnc: true,
+            is_old_0: false,
+            old_key: EMPTY_HASH,
+            old_value: vec![0; key.len()],
+        };
+        match node {
+            Node::Leaf { key: k, value, .. } if k == key => {
+                proof.value = value.clone();
+                proof.fnc = false;
+            }
+            Node::Leaf { key: k, value, .. } => {
+                proof.old_key = to_hash(k).expect("leaf keys are as long as the key");
+                proof.old_value = value.clone();
+                proof.value = value.clone();
+            }
+            _ => proof.is_old_0 = true,
+        }
+        Ok(proof)
+    }
+
+    fn check_key(&self, key: &[u8]) -> Result<(), TreeError> {
+        if key.len() > self.key_len() {
+            return Err(TreeError::KeyTooLong {
+                key_len: key.len(),
+                max_len: self.key_len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Walks down the path of `key`, returning the siblings found on the way and the
+    /// leaf or empty node where the path ends.
+    fn down(&self, key: &[u8]) -> (Vec<Hash>, &Node) {
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        let mut lvl = 0;
+        while let Node::Intermediate { left, right, .. } = node {
+            let (next, sibling) = if path_bit(key, lvl) {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            siblings.push(sibling.hash());
+            node = next;
+            lvl += 1;
+        }
+        (siblings, node)
+    }
+}
+
+/// Zero pads little-endian `bytes` to a [`Hash`], if they fit.
+fn to_hash(bytes: &[u8]) -> Option<Hash> {
+    let mut hash = EMPTY_HASH;
+    hash.get_mut(..bytes.len())?.copy_from_slice(bytes);
+    Some(hash)
+}
+
+fn add_at(
+    node: &mut Node,
+    key: &[u8],
+    value: &[u8],
+    lvl: usize,
+    max_levels: usize,
+) -> Result<(), TreeError> {
+    match node {
+        Node::Empty => *node = Node::new_leaf(key, value),
+        Node::Leaf { key: old_key, .. } => {
+            if old_key == key {
+                return Err(TreeError::KeyAlreadyExists);
+            }
+            // go down until the paths of both keys diverge
+            let div = (lvl..max_levels)
+                .find(|&i| path_bit(old_key, i) != path_bit(key, i))
+                .ok_or(TreeError::MaxLevelsReached)?;
+            let old = core::mem::replace(node, Node::Empty);
+            *node = push_down(old, Node::new_leaf(key, value), key, lvl, div);
+        }
+        Node::Intermediate { left, right, hash } => {
+            let child = if path_bit(key, lvl) {
+                &mut **right
+            } else {
+                &mut **left
+            };
+            add_at(child, key, value, lvl + 1, max_levels)?;
+            *hash = Blake3
+                .node_hash(&left.hash(), &right.hash())
+                .expect(BLAKE3_INFALLIBLE);
+        }
+    }
+    Ok(())
+}
+
+fn push_down(old: Node, new: Node, key: &[u8], lvl: usize, div: usize) -> Node {
+    if lvl == div {
+        return if path_bit(key, lvl) {
+            Node::new_intermediate(old, new)
+        } else {
+            Node::new_intermediate(new, old)
+        };
+    }
+    let child = push_down(old, new, key, lvl + 1, div);
+    if path_bit(key, lvl) {
+        Node::new_intermediate(Node::Empty, child)
+    } else {
+        Node::new_intermediate(child, Node::Empty)
+    }
+}
+
+fn update_at(node: &mut Node, key: &[u8], value: &[u8], lvl: usize) -> Result<(), TreeError> {
+    match node {
+        Node::Leaf { key: k, .. } if k == key => *node = Node::new_leaf(key, value),
+        Node::Intermediate { left, right, hash } => {
+            let child = if path_bit(key, lvl) {
+                &mut **right
+            } else {
+                &mut **left
+            };
+            update_at(child, key, value, lvl + 1)?;
+            *hash = Blake3
+                .node_hash(&left.hash(), &right.hash())
+                .expect(BLAKE3_INFALLIBLE);
+        }
+        _ => return Err(TreeError::KeyNotFound),
+    }
+    Ok(())
+}
+
+fn delete_at(node: &mut Node, key: &[u8], lvl: usize) -> Result<(), TreeError> {
+    match node {
+        Node::Leaf { key: k, .. } if k == key => *node = Node::Empty,
+        Node::Intermediate { left, right, hash } => {
+            let child = if path_bit(key, lvl) {
+                &mut **right
+            } else {
+                &mut **left
+            };
+            delete_at(child, key, lvl + 1)?;
+            match (&**left, &**right) {
+                // a single leaf left in this subtree moves up to take its place
+                (Node::Empty, Node::Leaf { .. }) | (Node::Empty, Node::Empty) => {
+                    *node = core::mem::replace(&mut **right, Node::Empty)
+                }
+                (Node::Leaf { .. }, Node::Empty) => {
+                    *node = core::mem::replace(&mut **left, Node::Empty)
+                }
+                _ => {
+                    *hash = Blake3
+                        .node_hash(&left.hash(), &right.hash())
+                        .expect(BLAKE3_INFALLIBLE)
+                }
+            }
+        }
+        _ => return Err(TreeError::KeyNotFound),
+    }
+    Ok(())
+}
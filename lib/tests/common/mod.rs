@@ -0,0 +1,24 @@
+// each test crate uses its own subset of these helpers
+#![allow(dead_code)]
+
+use smtverifier::{MerkleProof, MerkleProofFromFile, VerifyError};
+
+/// Reads the JSON proof `tests/testdata/<name>.json`.
+pub fn read_vector(name: &str) -> String {
+    let path = format!(
+        "{}/tests/testdata/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    std::fs::read_to_string(path).unwrap()
+}
+
+pub fn parse(json: &str) -> Result<MerkleProof, VerifyError> {
+    serde_json::from_str::<MerkleProofFromFile>(json)
+        .unwrap()
+        .try_into()
+}
+
+pub fn to_json(proof: MerkleProof) -> String {
+    serde_json::to_string(&MerkleProofFromFile::from(proof)).unwrap()
+}
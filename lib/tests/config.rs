@@ -0,0 +1,104 @@
+//! The verifier checks proofs against an explicit [`TreeConfig`] instead of inferring
+//! the key length from the number of siblings.
+
+use smtverifier::{verify_circom_proof, Blake3, Tree, TreeConfig, VerifyError, EMPTY_HASH};
+
+fn tree(max_levels: usize, keys: &[&[u8]]) -> Tree {
+    let mut tree = Tree::new(max_levels);
+    for (i, key) in keys.iter().enumerate() {
+        tree.add(key, &[i as u8 + 1]).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn key_len_does_not_depend_on_siblings() {
+    // 9 levels need 2-byte keys, while ceil((9 - 1) / 8) is 1
+    let tree = tree(9, &[&[0, 0], &[0, 1], &[1, 0]]);
+    assert_eq!(tree.config().key_len, 2);
+    for key in [[0, 0], [0, 1], [1, 0], [1, 1]] {
+        let proof = tree.generate_circom_verifier_proof(&key).unwrap();
+        verify_circom_proof(&tree.config(), &proof).unwrap();
+    }
+}
+
+#[test]
+fn trimmed_siblings_are_empty() {
+    let tree = tree(8, &[&[1], &[2], &[3]]);
+    let mut proof = tree.generate_circom_verifier_proof(&[2]).unwrap();
+    let depth = proof
+        .siblings
+        .iter()
+        .rposition(|s| *s != EMPTY_HASH)
+        .unwrap();
+    proof.siblings.truncate(depth + 1);
+    verify_circom_proof(&tree.config(), &proof).unwrap();
+}
+
+#[test]
+fn too_many_siblings() {
+    let tree = tree(8, &[&[1], &[2]]);
+    let mut proof = tree.generate_circom_verifier_proof(&[1]).unwrap();
+    proof.siblings.push(EMPTY_HASH);
+    assert_eq!(
+        verify_circom_proof(&tree.config(), &proof),
+        Err(VerifyError::TooManySiblings {
+            siblings: 9,
+            max_levels: 8
+        })
+    );
+}
+
+#[test]
+fn key_wider_than_the_tree() {
+    let tree = tree(8, &[&[1], &[2]]);
+    let mut proof = tree.generate_circom_verifier_proof(&[1]).unwrap();
+    proof.key[1] = 1;
+    assert_eq!(
+        verify_circom_proof(&tree.config(), &proof),
+        Err(VerifyError::KeyTooWide {
+            key_bits: 9,
+            max_bits: 8
+        })
+    );
+}
+
+#[test]
+fn invalid_config() {
+    let tree = tree(8, &[&[1]]);
+    let proof = tree.generate_circom_verifier_proof(&[1]).unwrap();
+    for (max_levels, key_len) in [(0, 1), (257, 32), (8, 0), (8, 33)] {
+        let config = TreeConfig {
+            max_levels,
+            key_len,
+            hasher: Blake3,
+        };
+        assert_eq!(
+            verify_circom_proof(&config, &proof),
+            Err(VerifyError::InvalidConfig {
+                max_levels,
+                key_len
+            })
+        );
+    }
+}
+
+#[test]
+fn keys_longer_than_the_levels() {
+    let config = TreeConfig {
+        key_len: 2,
+        ..TreeConfig::new(8, Blake3)
+    };
+    let mut tree = Tree::with_config(config);
+    assert_eq!(tree.key_len(), 2);
+    assert_eq!(tree.config(), config);
+    tree.add(&[1, 0], &[1]).unwrap();
+    assert!(tree.add(&[1, 0, 0], &[1]).is_err());
+
+    let proof = tree.generate_circom_verifier_proof(&[1, 0]).unwrap();
+    verify_circom_proof(&config, &proof).unwrap();
+    assert!(matches!(
+        verify_circom_proof(&TreeConfig::new(8, Blake3), &proof),
+        Err(VerifyError::RootMismatch { .. })
+    ));
+}
@@ -0,0 +1,203 @@
+//! Golden tests for the verifier, checking both that the proofs are accepted and the root
+//! they compute.
+//!
+//! `merkleproof.json` and `merkleproof_nonexistence.json` are the exact vectors pinned by
+//! `main_test.go`, which arbo checks against a circom circuit. They are the only vectors
+//! that come from arbo: the ones in `testdata/regression` are written by [`Tree`] and are
+//! checked in `state_tree.rs`.
+
+mod common;
+
+use alloy_primitives::{keccak256, U256};
+use alloy_sol_types::SolType;
+use common::{parse, read_vector, to_json};
+use smtverifier::smtverifier::LevelTrace;
+use smtverifier::{
+    compute_root, hash_to_u256, trace_circom_proof, verify_circom_proof, Blake3, OutputMode,
+    PublicValuesStruct, RootKeyPublicValuesStruct, RootPublicValuesStruct,
+    RootValueHashPublicValuesStruct, Tree, TreeConfig, TreeHasher, VerifyError,
+};
+
+const MOCK_ROOT: &str =
+    "21347616572972183420218629198567600327562992672406197041775040089740136115868";
+
+/// The 4-level tree of `TestMockProof`.
+fn mock_tree() -> Tree {
+    let mut tree = Tree::new(4);
+    for (key, value) in [(1, 11), (2, 22), (3, 33), (4, 44)] {
+        tree.add(&[key], &[value]).unwrap();
+    }
+    tree
+}
+
+fn check_vector(config: &TreeConfig, name: &str, root: &str) {
+    let proof = parse(&read_vector(name)).unwrap();
+    verify_circom_proof(config, &proof).unwrap();
+    assert_eq!(hash_to_u256(&proof.root).to_string(), root);
+    assert_eq!(
+        hash_to_u256(&compute_root(config, &proof).unwrap()).to_string(),
+        root
+    );
+}
+
+#[test]
+fn mock_inclusion() {
+    check_vector(&TreeConfig::new(4, Blake3), "merkleproof", MOCK_ROOT);
+    let proof = parse(&read_vector("merkleproof")).unwrap();
+    assert!(!proof.fnc);
+    assert_eq!((proof.key[0], proof.value.as_slice()), (2, &[22][..]));
+}
+
+#[test]
+fn mock_exclusion() {
+    check_vector(
+        &TreeConfig::new(4, Blake3),
+        "merkleproof_nonexistence",
+        MOCK_ROOT,
+    );
+    let proof = parse(&read_vector("merkleproof_nonexistence")).unwrap();
+    assert!(proof.fnc && !proof.is_old_0);
+    assert_eq!((proof.key[0], proof.old_key[0]), (5, 1));
+    assert_eq!(proof.old_value, [11]);
+}
+
+#[test]
+fn mock_tree_matches_arbo() {
+    let tree = mock_tree();
+    assert_eq!(hash_to_u256(&tree.root()).to_string(), MOCK_ROOT);
+    for (key, name) in [(2, "merkleproof"), (5, "merkleproof_nonexistence")] {
+        let proof = tree.generate_circom_verifier_proof(&[key]).unwrap();
+        assert_eq!(to_json(proof), read_vector(name));
+    }
+}
+
+#[test]
+fn mock_tampered() {
+    let config = TreeConfig::new(4, Blake3);
+    let proof = parse(&read_vector("merkleproof")).unwrap();
+
+    let mut wrong_value = proof.clone();
+    wrong_value.value = vec![23];
+    let mut wrong_sibling = proof.clone();
+    wrong_sibling.siblings[1][0] ^= 1;
+    let mut wrong_key = proof;
+    wrong_key.key[0] = 3;
+
+    for proof in [wrong_value, wrong_sibling, wrong_key] {
+        assert!(matches!(
+            verify_circom_proof(&config, &proof),
+            Err(VerifyError::RootMismatch { .. })
+        ));
+    }
+}
+
+#[test]
+fn public_values() {
+    for (name, fnc) in [("merkleproof", false), ("merkleproof_nonexistence", true)] {
+        let proof = parse(&read_vector(name)).unwrap();
+        let config = TreeConfig::new(4, Blake3);
+        let bytes =
+            PublicValuesStruct::abi_encode(&PublicValuesStruct::new((&config).into(), &proof));
+        let decoded = PublicValuesStruct::abi_decode(&bytes, true).unwrap();
+        assert_eq!(decoded.config.maxLevels, U256::from(4));
+        assert_eq!(decoded.config.keyLen, U256::from(1));
+        assert_eq!(decoded.config.hasher, "blake3");
+        let decoded = decoded.proof;
+        assert_eq!(decoded.root.to_string(), MOCK_ROOT);
+        assert_eq!(decoded.key, hash_to_u256(&proof.key));
+        assert_eq!(decoded.fnc, fnc);
+        assert_eq!(decoded.value.as_ref(), if fnc { &[][..] } else { &[22] });
+        assert_eq!(decoded.siblings.len(), 4);
+    }
+}
+
+#[test]
+fn output_modes() {
+    let proof = parse(&read_vector("merkleproof")).unwrap();
+    let config = TreeConfig::new(4, Blake3);
+
+    let bytes = OutputMode::Full.encode(&config, &proof);
+    assert_eq!(
+        bytes,
+        PublicValuesStruct::abi_encode(&PublicValuesStruct::new((&config).into(), &proof))
+    );
+
+    let bytes = OutputMode::RootKey.encode(&config, &proof);
+    let decoded = RootKeyPublicValuesStruct::abi_decode(&bytes, true).unwrap();
+    assert_eq!(decoded.root.to_string(), MOCK_ROOT);
+    assert_eq!(decoded.key, hash_to_u256(&proof.key));
+    assert!(!decoded.fnc);
+
+    let bytes = OutputMode::RootValueHash.encode(&config, &proof);
+    let decoded = RootValueHashPublicValuesStruct::abi_decode(&bytes, true).unwrap();
+    assert_eq!(decoded.root.to_string(), MOCK_ROOT);
+    assert_eq!(decoded.valueHash, keccak256([22]));
+
+    let bytes = OutputMode::Root.encode(&config, &proof);
+    let decoded = RootPublicValuesStruct::abi_decode(&bytes, true).unwrap();
+    assert_eq!(decoded.root.to_string(), MOCK_ROOT);
+    assert_eq!(decoded.config.keyLen, U256::from(1));
+
+    for (name, mode) in [
+        ("full", OutputMode::Full),
+        ("root-key", OutputMode::RootKey),
+        ("root-value-hash", OutputMode::RootValueHash),
+        ("root", OutputMode::Root),
+    ] {
+        assert_eq!(name.parse(), Ok(mode));
+    }
+}
+
+#[test]
+fn mock_traces() {
+    let config = TreeConfig::new(4, Blake3);
+    let flags = |l: &LevelTrace| (l.top, l.inew, l.iold, l.i0, l.na);
+    for (name, lrbits, leaf, shown) in [
+        (
+            "merkleproof",
+            [false, true],
+            (false, true, false, false, false),
+            "level 2 new",
+        ),
+        (
+            "merkleproof_nonexistence",
+            [true, false],
+            (false, false, true, false, false),
+            "level 2 old",
+        ),
+    ] {
+        let proof = parse(&read_vector(name)).unwrap();
+        let trace = trace_circom_proof(&config, &proof).unwrap();
+        assert_eq!(trace.root, compute_root(&config, &proof).unwrap());
+        assert_eq!(trace.root, proof.root);
+
+        // the path goes through two nodes down to the leaf, then through empty levels
+        assert_eq!(trace.levels.len(), 5, "{}", name);
+        for (i, level) in trace.levels.iter().enumerate() {
+            match i {
+                0 | 1 => {
+                    assert_eq!(flags(level), (true, false, false, false, false));
+                    assert_eq!(level.lrbit, lrbits[i]);
+                    assert_eq!(level.sibling, proof.siblings[i]);
+                    let (left, right) = match level.lrbit {
+                        false => (&level.child, &level.sibling),
+                        true => (&level.sibling, &level.child),
+                    };
+                    assert_eq!(level.hash, Blake3.node_hash(left, right).unwrap());
+                    assert_eq!(level.child, trace.levels[i + 1].hash);
+                }
+                2 => {
+                    assert_eq!(flags(level), leaf);
+                    let (key, value) = match proof.fnc {
+                        false => (&proof.key, &proof.value),
+                        true => (&proof.old_key, &proof.old_value),
+                    };
+                    assert_eq!(level.hash, Blake3.leaf_hash(&key[..1], value).unwrap());
+                }
+                _ => assert_eq!(flags(level), (false, false, false, false, true)),
+            }
+        }
+        assert_eq!(trace.levels[0].hash, trace.root);
+        assert!(trace.to_string().contains(shown), "{}", trace);
+    }
+}
@@ -0,0 +1,159 @@
+//! Roots of the tree of arbo's `TestAddTestVectors` with every hash function: keys 1, 33
+//! and 1234 holding 2, 44 and 9876, all as 32-byte little-endian numbers, in a 256-level
+//! tree.
+//!
+//! The SHA-256 and Poseidon roots are the ones pinned by arbo (the Poseidon ones come from
+//! circomlib's `smt.js`). arbo has no vectors for Blake2b, so its roots were computed with
+//! Python's `hashlib.blake2b` using the same layout, and Keccak-256, which arbo doesn't
+//! have, is checked against alloy's implementation. Run with
+//! `--features sha256,keccak256,blake2b,poseidon` to cover them all.
+
+use smtverifier::{Blake3, Hash, TreeHasher, EMPTY_HASH};
+
+const LEAVES: [(u64, u64); 3] = [(1, 2), (33, 44), (1234, 9876)];
+
+fn to_bytes(i: u64) -> Hash {
+    let mut bytes = EMPTY_HASH;
+    bytes[..8].copy_from_slice(&i.to_le_bytes());
+    bytes
+}
+
+/// Root of a tree holding `leaves`, built top-down from the key bits: a subtree with a
+/// single leaf is that leaf, as arbo places it as high as possible.
+fn root<H: TreeHasher>(hasher: &H, leaves: &[(Hash, Hash)], lvl: usize) -> Hash {
+    match leaves {
+        [] => EMPTY_HASH,
+        [(key, value)] => hasher.leaf_hash(key, value).unwrap(),
+        _ => {
+            let (right, left): (Vec<_>, Vec<_>) = leaves
+                .iter()
+                .partition(|(key, _)| key[lvl / 8] >> (lvl % 8) & 1 == 1);
+            hasher
+                .node_hash(
+                    &root(hasher, &left, lvl + 1),
+                    &root(hasher, &right, lvl + 1),
+                )
+                .unwrap()
+        }
+    }
+}
+
+/// Roots after adding each of [`LEAVES`], as decimal numbers.
+fn roots<H: TreeHasher>(hasher: &H) -> Vec<String> {
+    (1..=LEAVES.len())
+        .map(|n| {
+            let leaves: Vec<(Hash, Hash)> = LEAVES[..n]
+                .iter()
+                .map(|&(key, value)| (to_bytes(key), to_bytes(value)))
+                .collect();
+            num_bigint::BigUint::from_bytes_le(&root(hasher, &leaves, 0)).to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn blake3_matches_tree() {
+    let mut tree = smtverifier::Tree::new(256);
+    for (i, &(key, value)) in LEAVES.iter().enumerate() {
+        tree.add(&to_bytes(key), &to_bytes(value)).unwrap();
+        let root = num_bigint::BigUint::from_bytes_le(&tree.root()).to_string();
+        assert_eq!(root, roots(&Blake3)[i]);
+    }
+}
+
+#[cfg(feature = "sha256")]
+#[test]
+fn sha256() {
+    assert_eq!(
+        roots(&smtverifier::hasher::Sha256),
+        [
+            "46910109172468462938850740851377282682950237270676610513794735904325820156367",
+            "59481735341404520835410489183267411392292882901306595567679529387376287440550",
+            "20573794434149960984975763118181266662429997821552560184909083010514790081771",
+        ]
+    );
+}
+
+#[cfg(feature = "poseidon")]
+#[test]
+fn poseidon() {
+    assert_eq!(
+        roots(&smtverifier::hasher::Poseidon),
+        [
+            "13578938674299138072471463694055224830892726234048532520316387704878000008795",
+            "5412393676474193513566895793055462193090331607895808993925969873307089394741",
+            "14204494359367183802864593755198662203838502594566452929175967972147978322084",
+        ]
+    );
+}
+
+#[cfg(feature = "blake2b")]
+#[test]
+fn blake2b() {
+    assert_eq!(
+        roots(&smtverifier::hasher::Blake2b),
+        [
+            "95458021904201293914450973986054294852763024351530111103355182127890428892685",
+            "60399203266375099816135699796830505612065831070592820830454872645573283356918",
+            "63450641929765516162123989283378081755542930909161856772223517875865053780430",
+        ]
+    );
+}
+
+#[cfg(feature = "keccak256")]
+#[test]
+fn keccak256() {
+    use alloy_primitives::keccak256;
+    use smtverifier::VerifyError;
+
+    struct AlloyKeccak;
+
+    impl TreeHasher for AlloyKeccak {
+        const NAME: &'static str = "keccak256";
+
+        fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+            Ok(keccak256([key, value, &[1]].concat()).0)
+        }
+
+        fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+            Ok(keccak256([&left[..], &right[..]].concat()).0)
+        }
+    }
+
+    assert_eq!(roots(&smtverifier::hasher::Keccak256), roots(&AlloyKeccak));
+}
+
+#[cfg(feature = "poseidon")]
+#[test]
+fn poseidon_inputs() {
+    use smtverifier::{hasher::Poseidon, verify, TreeConfig, VerifyError};
+
+    let config = TreeConfig::new(256, Poseidon);
+    let leaves: Vec<(Hash, Hash)> = LEAVES
+        .iter()
+        .map(|&(key, value)| (to_bytes(key), to_bytes(value)))
+        .collect();
+    let tree_root = root(&Poseidon, &leaves, 0);
+    // 1 and 33 share their first 5 bits, 1234 goes left at the root
+    let mut siblings = vec![EMPTY_HASH; 6];
+    siblings[0] = root(&Poseidon, &leaves[2..], 1);
+    siblings[5] = root(&Poseidon, &leaves[1..2], 6);
+
+    // values are numbers as in arbo, so trailing zeros don't count, and there is no old
+    // leaf to hash in an inclusion proof
+    let long = [&[2][..], &[0; 40]].concat();
+    for value in [&to_bytes(2)[..], &[2], &long] {
+        verify(&config, &tree_root, &to_bytes(1), value, &siblings).unwrap();
+    }
+
+    // inputs that are not field elements are rejected instead of panicking
+    assert_eq!(
+        verify(&config, &tree_root, &to_bytes(1), &[1; 33], &siblings),
+        Err(VerifyError::InvalidHashInput)
+    );
+    siblings[0] = [0xff; 32];
+    assert_eq!(
+        verify(&config, &tree_root, &to_bytes(1), &[2], &siblings),
+        Err(VerifyError::InvalidHashInput)
+    );
+}
@@ -0,0 +1,172 @@
+//! Multiproofs merge the proofs of several keys of a tree, storing and hashing the nodes
+//! their paths share only once.
+
+mod common;
+
+use common::{parse, read_vector};
+use smtverifier::multiproof::compute_multiproof_root;
+use smtverifier::{
+    verify_circom_proof, verify_multiproof, Blake3, Hash, MerkleProof, MultiProof, Tree,
+    TreeConfig, TreeHasher, VerifyError,
+};
+use std::cell::Cell;
+
+/// Blake3, counting how many times it is called.
+#[derive(Default)]
+struct CountingHasher {
+    calls: Cell<usize>,
+}
+
+impl TreeHasher for CountingHasher {
+    const NAME: &'static str = "blake3";
+
+    fn leaf_hash(&self, key: &[u8], value: &[u8]) -> Result<Hash, VerifyError> {
+        self.calls.set(self.calls.get() + 1);
+        Blake3.leaf_hash(key, value)
+    }
+
+    fn node_hash(&self, left: &Hash, right: &Hash) -> Result<Hash, VerifyError> {
+        self.calls.set(self.calls.get() + 1);
+        Blake3.node_hash(left, right)
+    }
+}
+
+fn state1_proofs() -> Vec<MerkleProof> {
+    (0..6)
+        .map(|key| {
+            parse(&read_vector(&format!(
+                "regression/state1merkleproof{}",
+                key
+            )))
+            .unwrap()
+        })
+        .collect()
+}
+
+fn tree(max_levels: usize, n: u8) -> Tree {
+    let mut tree = Tree::new(max_levels);
+    for key in 0..n {
+        tree.add(&[key.wrapping_mul(37)], &[key, 1]).unwrap();
+    }
+    tree
+}
+
+fn multiproof(tree: &Tree, keys: &[u8]) -> MultiProof {
+    let proofs: Vec<MerkleProof> = keys
+        .iter()
+        .map(|key| tree.generate_circom_verifier_proof(&[*key]).unwrap())
+        .collect();
+    MultiProof::from_proofs(&proofs).unwrap()
+}
+
+#[test]
+fn state_slots_share_nodes() {
+    let proofs = state1_proofs();
+    let multiproof = MultiProof::from_proofs(&proofs).unwrap();
+
+    let config = TreeConfig::new(8, CountingHasher::default());
+    verify_multiproof(&config, &multiproof).unwrap();
+    let multi_calls = config.hasher.calls.take();
+    for proof in &proofs {
+        verify_circom_proof(&config, proof).unwrap();
+    }
+    let single_calls = config.hasher.calls.take();
+    assert!(
+        multi_calls < single_calls,
+        "{} hashes, {} with one proof per key",
+        multi_calls,
+        single_calls
+    );
+
+    let siblings: usize = proofs.iter().map(|p| p.siblings.len()).sum();
+    assert!(multiproof.nodes.len() < siblings);
+}
+
+#[test]
+fn subsets_of_keys() {
+    let tree = tree(8, 40);
+    let config = tree.config();
+    for keys in [
+        &[0][..],
+        &[37, 74],
+        &[1, 2, 3, 4, 5],
+        &[0, 37, 74, 111, 148, 185, 222, 3],
+        &[255, 254, 128, 127],
+    ] {
+        let multiproof = multiproof(&tree, keys);
+        verify_multiproof(&config, &multiproof).unwrap();
+        assert_eq!(
+            compute_multiproof_root(&config, &multiproof),
+            Ok(tree.root())
+        );
+    }
+
+    let all: Vec<u8> = (0..=u8::MAX).collect();
+    verify_multiproof(&config, &multiproof(&tree, &all)).unwrap();
+    verify_multiproof(&Tree::new(8).config(), &multiproof(&Tree::new(8), &[1, 2])).unwrap();
+}
+
+#[test]
+fn shared_leaves_are_hashed_once() {
+    // 5 is not in the tree and its path ends in the leaf of 37
+    let tree = tree(8, 20);
+    let config = TreeConfig::new(8, CountingHasher::default());
+    verify_multiproof(&config, &multiproof(&tree, &[37])).unwrap();
+    let included = config.hasher.calls.take();
+    verify_multiproof(&config, &multiproof(&tree, &[37, 5])).unwrap();
+    assert_eq!(config.hasher.calls.take(), included);
+}
+
+#[test]
+fn tampered_multiproofs() {
+    let tree = tree(8, 20);
+    let config = tree.config();
+    let multiproof = multiproof(&tree, &[0, 37, 5]);
+
+    let mut wrong_value = multiproof.clone();
+    wrong_value.leaves[0].value[1] = 2;
+    assert!(matches!(
+        verify_multiproof(&config, &wrong_value),
+        Err(VerifyError::RootMismatch { .. })
+    ));
+
+    // 5 is not in the tree and its path ends in the leaf of 37
+    let mut disagreeing_leaf = multiproof.clone();
+    disagreeing_leaf.leaves[1].value[1] = 2;
+
+    let mut extra_node = multiproof.clone();
+    extra_node.nodes.push(tree.root());
+    let mut missing_node = multiproof.clone();
+    missing_node.nodes.pop();
+    let mut repeated_key = multiproof.clone();
+    repeated_key.leaves.push(repeated_key.leaves[0].clone());
+    let mut wrong_depth = multiproof.clone();
+    wrong_depth.leaves[0].depth += 1;
+    for proof in [disagreeing_leaf, extra_node, missing_node, repeated_key] {
+        assert_eq!(
+            verify_multiproof(&config, &proof),
+            Err(VerifyError::InvalidMultiProof)
+        );
+    }
+    assert!(verify_multiproof(&config, &wrong_depth).is_err());
+
+    let mut wrong_fnc = multiproof;
+    wrong_fnc.leaves[2].fnc = false;
+    assert!(verify_multiproof(&config, &wrong_fnc).is_err());
+}
+
+#[test]
+fn proofs_of_other_trees_are_not_merged() {
+    let proofs = [
+        tree(8, 20).generate_circom_verifier_proof(&[0]).unwrap(),
+        tree(8, 21).generate_circom_verifier_proof(&[37]).unwrap(),
+    ];
+    assert_eq!(
+        MultiProof::from_proofs(&proofs),
+        Err(VerifyError::InvalidMultiProof)
+    );
+    assert_eq!(
+        MultiProof::from_proofs(&[]),
+        Err(VerifyError::InvalidMultiProof)
+    );
+}
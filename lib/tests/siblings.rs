@@ -0,0 +1,125 @@
+//! Siblings packed as arbo's `PackSiblings` does, as used by arbo's `CheckProof` and on
+//! the zkVM stdin.
+
+mod common;
+
+use common::{parse, read_vector};
+use smtverifier::{
+    pack_siblings, unpack_siblings, verify_circom_proof, Blake3, Hash, MerkleProof, PackError,
+    TreeConfig, EMPTY_HASH,
+};
+
+/// `arbo.PackSiblings` of the siblings of `merkleproof.json`, as printed by `TestMockProof`:
+/// 69 bytes in total, a 1-byte bitmap with the first two siblings set, and those two.
+const MOCK_PACKED: &str = "4500010003\
+    68081ab3afa7554d7fe01f65b6720fd6d2f13a327be0df033a373213a9acaec5\
+    1c2654767c998c3a4453c24d1bb162b773eabf864c2c215c8cbd65cb9d0eb0dd";
+
+fn sibling(i: u8) -> Hash {
+    [i; 32]
+}
+
+#[test]
+fn mock_proof_packed_as_arbo() {
+    let proof = parse(&read_vector("merkleproof")).unwrap();
+    let packed = proof.packed_siblings().unwrap();
+    assert_eq!(hex::encode(&packed), MOCK_PACKED);
+
+    // arbo drops the empty siblings after the last non-empty one
+    assert_eq!(unpack_siblings(&packed).unwrap(), proof.siblings[..2]);
+
+    let unpacked = MerkleProof::from_packed_siblings(
+        proof.root,
+        proof.key,
+        proof.value.clone(),
+        &packed,
+        proof.siblings.len(),
+    )
+    .unwrap();
+    assert_eq!(unpacked.siblings, proof.siblings);
+    verify_circom_proof(&TreeConfig::new(4, Blake3), &unpacked).unwrap();
+}
+
+#[test]
+fn round_trip() {
+    let mut many = vec![EMPTY_HASH; 256];
+    for i in (0..256).step_by(3) {
+        many[i] = sibling((i % 255) as u8 + 1);
+    }
+    for siblings in [
+        vec![],
+        vec![EMPTY_HASH; 4],
+        vec![sibling(1)],
+        vec![EMPTY_HASH, sibling(1), EMPTY_HASH, sibling(2)],
+        // the bitmap takes a second byte from the 9th sibling on
+        vec![
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            EMPTY_HASH,
+            sibling(9),
+        ],
+        many,
+    ] {
+        let packed = pack_siblings(&siblings).unwrap();
+        assert_eq!(
+            packed.len(),
+            u16::from_le_bytes([packed[0], packed[1]]) as usize
+        );
+
+        let mut unpacked = unpack_siblings(&packed).unwrap();
+        let last = siblings.iter().rposition(|s| *s != EMPTY_HASH);
+        assert_eq!(unpacked.len(), last.map_or(0, |i| i + 1));
+        unpacked.resize(siblings.len(), EMPTY_HASH);
+        assert_eq!(unpacked, siblings);
+    }
+}
+
+#[test]
+fn malformed_packed_siblings() {
+    let packed = pack_siblings(&[sibling(1), EMPTY_HASH, sibling(2)]).unwrap();
+    let with_header = |full_len: u16, bitmap_len: u16, rest: &[u8]| -> Vec<u8> {
+        [&full_len.to_le_bytes()[..], &bitmap_len.to_le_bytes(), rest].concat()
+    };
+
+    for (bytes, error) in [
+        (vec![], PackError::InvalidLength),
+        (packed[..3].to_vec(), PackError::InvalidLength),
+        // the full length doesn't match the bytes
+        (
+            packed[..packed.len() - 1].to_vec(),
+            PackError::InvalidLength,
+        ),
+        ([&packed[..], &[0]].concat(), PackError::InvalidLength),
+        // a bitmap longer than what follows the header
+        (with_header(5, 2, &[0b101]), PackError::InvalidBitmapLength),
+        (
+            with_header(packed.len() as u16, packed.len() as u16, &packed[4..]),
+            PackError::InvalidBitmapLength,
+        ),
+        // a sibling cut short
+        (
+            with_header(36, 1, &[1; 32]),
+            PackError::InvalidSiblingsLength,
+        ),
+    ] {
+        assert_eq!(
+            unpack_siblings(&bytes),
+            Err(error),
+            "{}",
+            hex::encode(&bytes)
+        );
+    }
+
+    // three levels don't fit in two
+    let proof = MerkleProof::from_packed_siblings(EMPTY_HASH, EMPTY_HASH, vec![], &packed, 2);
+    assert_eq!(proof.unwrap_err(), PackError::TooManySiblings);
+
+    // the full length doesn't fit in its 2 bytes
+    let siblings: Vec<Hash> = (0..2048).map(|_| sibling(1)).collect();
+    assert_eq!(pack_siblings(&siblings), Err(PackError::TooLong));
+}
@@ -0,0 +1,102 @@
+//! Verification of groth16 and plonk SP1 proofs without the prover.
+//!
+//! The fixtures hold the Fibonacci proofs that `sp1-verifier` tests itself with, converted
+//! from its `test_binaries` to the fixture layout of the evm binary.
+#![cfg(feature = "snark")]
+
+use serde::Deserialize;
+use smtverifier::{verify_snark, ProofSystem, SnarkError};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fixture {
+    vkey: String,
+    public_values: String,
+    proof: String,
+}
+
+fn fixture(system: ProofSystem) -> (String, Vec<u8>, Vec<u8>) {
+    let name = match system {
+        ProofSystem::Groth16 => "groth16",
+        ProofSystem::Plonk => "plonk",
+    };
+    let path = format!(
+        "{}/tests/testdata/snark/fibonacci-{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let decode = |s: &str| hex::decode(s.trim_start_matches("0x")).unwrap();
+    (
+        fixture.vkey,
+        decode(&fixture.public_values),
+        decode(&fixture.proof),
+    )
+}
+
+#[test]
+fn fixtures_verify() {
+    for system in [ProofSystem::Groth16, ProofSystem::Plonk] {
+        let (vkey, public_values, proof) = fixture(system);
+        let circuit_vk = system.circuit_vk();
+        verify_snark(system, &proof, &public_values, &vkey, circuit_vk).unwrap();
+
+        // other public values
+        let mut other_values = public_values.clone();
+        other_values[0] ^= 1;
+        assert!(verify_snark(system, &proof, &other_values, &vkey, circuit_vk).is_err());
+    }
+
+    // the proof of one system is not one of the other
+    let (vkey, public_values, proof) = fixture(ProofSystem::Groth16);
+    assert!(verify_snark(
+        ProofSystem::Plonk,
+        &proof,
+        &public_values,
+        &vkey,
+        ProofSystem::Plonk.circuit_vk()
+    )
+    .is_err());
+}
+
+#[test]
+fn wrong_vkey() {
+    // the proof of another program, as far as the verifier can tell. sp1-verifier drops the
+    // first byte of the vkey hash, so the last one is changed.
+    for system in [ProofSystem::Groth16, ProofSystem::Plonk] {
+        let (vkey, public_values, proof) = fixture(system);
+        let other_vkey = format!("{}00", &vkey[..vkey.len() - 2]);
+        assert_ne!(other_vkey, vkey);
+        let res = verify_snark(
+            system,
+            &proof,
+            &public_values,
+            &other_vkey,
+            system.circuit_vk(),
+        );
+        match system {
+            ProofSystem::Groth16 => assert!(matches!(res, Err(SnarkError::Groth16(_)))),
+            ProofSystem::Plonk => assert!(matches!(res, Err(SnarkError::Plonk(_)))),
+        }
+    }
+}
+
+#[test]
+fn malformed_inputs() {
+    for system in [ProofSystem::Groth16, ProofSystem::Plonk] {
+        let (vkey, public_values, proof) = fixture(system);
+        let circuit_vk = system.circuit_vk();
+        for (proof, vkey) in [
+            (&[][..], &vkey[..]),
+            (&proof[..4], &vkey),
+            (&proof[..proof.len() - 1], &vkey),
+            (&proof, ""),
+            (&proof, &vkey[2..]),
+        ] {
+            assert!(matches!(
+                verify_snark(system, proof, &public_values, vkey, circuit_vk),
+                Err(SnarkError::Malformed)
+            ));
+        }
+    }
+}
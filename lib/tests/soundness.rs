@@ -0,0 +1,204 @@
+//! Soundness harness: proofs taken from random trees are mutated, and the verifier must
+//! reject every mutated proof whose statement is false in the tree it came from.
+//!
+//! Some mutations keep the statement true (e.g. moving a non-inclusion proof to another
+//! absent key on the same path, or trimming empty siblings), so a mutated proof that is
+//! accepted is checked against the tree instead of being counted as a finding outright.
+//! Any panic, such as an out of bounds index in `level_ins`, fails the test.
+
+use proptest::prelude::*;
+use smtverifier::{
+    verify_circom_proof, verify_multiproof, verify_transition, Blake3, MerkleProof, MultiProof,
+    Operation, TransitionProof, Tree, TreeConfig, EMPTY_HASH,
+};
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    FlipSiblingBit { level: usize, bit: usize },
+    SwapSiblings { a: usize, b: usize },
+    FlipKeyBit { bit: usize },
+    FlipOldKeyBit { bit: usize },
+    FlipValueBit { bit: usize },
+    FlipRootBit { bit: usize },
+    ToggleFnc,
+    ToggleIsOld0,
+    TruncateSiblings { len: usize },
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        (0..8usize, 0..256usize).prop_map(|(level, bit)| Mutation::FlipSiblingBit { level, bit }),
+        (0..8usize, 0..8usize).prop_map(|(a, b)| Mutation::SwapSiblings { a, b }),
+        (0..16usize).prop_map(|bit| Mutation::FlipKeyBit { bit }),
+        (0..16usize).prop_map(|bit| Mutation::FlipOldKeyBit { bit }),
+        (0..24usize).prop_map(|bit| Mutation::FlipValueBit { bit }),
+        (0..256usize).prop_map(|bit| Mutation::FlipRootBit { bit }),
+        Just(Mutation::ToggleFnc),
+        Just(Mutation::ToggleIsOld0),
+        (0..8usize).prop_map(|len| Mutation::TruncateSiblings { len }),
+    ]
+}
+
+fn flip(bytes: &mut [u8], bit: usize) {
+    let len = bytes.len();
+    if len > 0 {
+        bytes[(bit / 8) % len] ^= 1 << (bit % 8);
+    }
+}
+
+fn apply(proof: &mut MerkleProof, mutation: &Mutation) {
+    // siblings may have been truncated by a previous mutation
+    let n = proof.siblings.len().max(1);
+    match *mutation {
+        Mutation::FlipSiblingBit { level, bit } => {
+            if let Some(sibling) = proof.siblings.get_mut(level % n) {
+                flip(sibling, bit)
+            }
+        }
+        Mutation::SwapSiblings { a, b } => {
+            if !proof.siblings.is_empty() {
+                proof.siblings.swap(a % n, b % n)
+            }
+        }
+        Mutation::FlipKeyBit { bit } => flip(&mut proof.key[..2], bit),
+        Mutation::FlipOldKeyBit { bit } => flip(&mut proof.old_key[..2], bit),
+        Mutation::FlipValueBit { bit } => {
+            if proof.fnc {
+                flip(&mut proof.old_value, bit)
+            } else {
+                flip(&mut proof.value, bit)
+            }
+        }
+        Mutation::FlipRootBit { bit } => flip(&mut proof.root, bit),
+        Mutation::ToggleFnc => proof.fnc = !proof.fnc,
+        Mutation::ToggleIsOld0 => proof.is_old_0 = !proof.is_old_0,
+        Mutation::TruncateSiblings { len } => proof.siblings.truncate(len),
+    }
+}
+
+/// Whether `proof` states something true about `tree`.
+fn holds(tree: &Tree, proof: &MerkleProof) -> bool {
+    if proof.root != tree.root() || proof.key[1..] != EMPTY_HASH[1..] {
+        return false;
+    }
+    match tree.get(&proof.key[..1]) {
+        Ok(value) => !proof.fnc && value == proof.value,
+        Err(_) => proof.fnc,
+    }
+}
+
+/// A tree with 1-byte keys and up to 3-byte values, and a key to prove.
+fn tree_and_key() -> impl Strategy<Value = (Tree, u8)> {
+    (
+        1..=8usize,
+        prop::collection::btree_map(
+            any::<u8>(),
+            prop::collection::vec(any::<u8>(), 1..=3),
+            0..24,
+        ),
+        any::<u8>(),
+    )
+        .prop_map(|(max_levels, leaves, key)| {
+            let mut tree = Tree::new(max_levels);
+            for (k, v) in &leaves {
+                // small trees can't hold keys that share a long path
+                let _ = tree.add(&[*k], v);
+            }
+            (tree, key)
+        })
+}
+
+proptest! {
+    #[test]
+    fn mutated_proofs_are_rejected(
+        (tree, key) in tree_and_key(),
+        mutations in prop::collection::vec(mutation(), 1..=3),
+    ) {
+        let config = tree.config();
+        let mut proof = tree.generate_circom_verifier_proof(&[key]).unwrap();
+        prop_assert!(verify_circom_proof(&config, &proof).is_ok());
+
+        for m in &mutations {
+            apply(&mut proof, m);
+        }
+        if verify_circom_proof(&config, &proof).is_ok() {
+            prop_assert!(holds(&tree, &proof), "accepted {:?}: {:?}", mutations, proof);
+        }
+    }
+
+    #[test]
+    fn multiproofs_are_sound(
+        (tree, _) in tree_and_key(),
+        keys in prop::collection::btree_set(any::<u8>(), 1..16),
+        node in any::<usize>(),
+        bit in 0..256usize,
+    ) {
+        let config = tree.config();
+        let proofs: Vec<MerkleProof> = keys
+            .iter()
+            .map(|key| tree.generate_circom_verifier_proof(&[*key]).unwrap())
+            .collect();
+        let multiproof = MultiProof::from_proofs(&proofs).unwrap();
+        prop_assert!(verify_multiproof(&config, &multiproof).is_ok());
+
+        if !multiproof.nodes.is_empty() {
+            let mut mutated = multiproof.clone();
+            let n = node % mutated.nodes.len();
+            flip(&mut mutated.nodes[n], bit);
+            prop_assert!(verify_multiproof(&config, &mutated).is_err());
+        }
+        let mut mutated = multiproof;
+        let n = node % mutated.leaves.len();
+        mutated.leaves[n].depth ^= 1;
+        prop_assert!(verify_multiproof(&config, &mutated).is_err());
+    }
+
+    #[test]
+    fn arbitrary_proofs_do_not_panic(
+        max_levels in 0..=10usize,
+        key_len in 0..=2usize,
+        siblings in prop::collection::vec(
+            prop_oneof![Just(EMPTY_HASH), any::<[u8; 32]>()],
+            0..=12,
+        ),
+        keys in any::<([u8; 2], [u8; 2])>(),
+        values in any::<(Vec<u8>, Vec<u8>)>(),
+        flags in any::<(bool, bool)>(),
+        op in prop_oneof![
+            Just(Operation::Nop),
+            Just(Operation::Update),
+            Just(Operation::Insert),
+            Just(Operation::Delete),
+        ],
+    ) {
+        let config = TreeConfig { max_levels, key_len, hasher: Blake3 };
+        let (mut key, mut old_key) = (EMPTY_HASH, EMPTY_HASH);
+        key[..2].copy_from_slice(&keys.0);
+        old_key[..2].copy_from_slice(&keys.1);
+
+        let proof = MerkleProof {
+            root: siblings.first().copied().unwrap_or(EMPTY_HASH),
+            key,
+            value: values.0.clone(),
+            siblings: siblings.clone(),
+            fnc: flags.0,
+            is_old_0: flags.1,
+            old_key,
+            old_value: values.1.clone(),
+        };
+        let _ = verify_circom_proof(&config, &proof);
+
+        let transition = TransitionProof {
+            old_root: proof.root,
+            new_root: EMPTY_HASH,
+            siblings,
+            old_key,
+            old_value: values.1,
+            is_old_0: flags.1,
+            new_key: key,
+            new_value: values.0,
+            op,
+        };
+        let _ = verify_transition(&config, &transition);
+    }
+}
@@ -0,0 +1,480 @@
+//! Regression tests for the state tree built by `main.go`: 1-byte keys holding values
+//! that are much longer than the key.
+//!
+//! The vectors in `testdata/regression` were written by [`Tree`], not by arbo, so they pin
+//! the output of this crate rather than arbo's. `state{1,2}merkleproof*.json` have the
+//! layout of the files written by `main.go`, with `valueLen` added. Its `Results` leaves
+//! (keys 4 and 5) are gob streams, which were encoded by hand from the rules of
+//! `encoding/gob` since there is no Go here to run `main.go`: the leaf hashes match arbo's
+//! only as far as that encoding does. `merkleproof256*.json` come from a tree shaped like
+//! the one of `TestProofWith256Levels`, with fixed bytes in place of its random ones.
+
+mod common;
+
+use alloy_primitives::keccak256;
+use alloy_sol_types::SolValue;
+use common::{parse, read_vector, to_json};
+use num_bigint::BigUint;
+use smtverifier::{
+    apply_transitions, check_proof_batch, compute_root, hash_to_u256, verify_circom_proof, Blake3,
+    MerkleProof, OutputMode, PublicValuesError, PublicValuesStruct, RootKeyPublicValuesStruct,
+    RootValueHashPublicValuesStruct, Statement, Tree, TreeConfig, VerifyError,
+};
+
+/// `Results{Votes: {{10, 5}}}.Bytes()` of `main.go`: the gob descriptors of `Results`,
+/// `[][]*big.Int`, `[]*big.Int`, `big.Int` and `types.BigInt`, then the value itself.
+const RESULTS_ADD: &[u8] = b"\
+    \x2c\xff\x81\x03\x01\x01\x07Results\x01\xff\x82\x00\x01\x02\
+    \x01\x05Votes\x01\xff\x88\x00\x01\x06Weight\x01\xff\x8a\x00\x00\x00\
+    \x1b\xff\x87\x02\x01\x01\x0c[][]*big.Int\x01\xff\x88\x00\x01\xff\x86\x00\x00\
+    \x0d\xff\x85\x02\x01\x02\xff\x86\x00\x01\xff\x84\x00\x00\
+    \x0a\xff\x83\x05\x01\x02\xff\x8c\x00\x00\x00\
+    \x0a\xff\x89\x05\x01\x02\xff\x8e\x00\x00\x00\
+    \x0c\xff\x82\x01\x01\x02\x02\x02\x0a\x02\x02\x05\x00";
+
+/// `Results{Votes: {{2, 0}}}.Bytes()`, which only differs from [`RESULTS_ADD`] by its value.
+const RESULTS_SUB: &[u8] = b"\
+    \x2c\xff\x81\x03\x01\x01\x07Results\x01\xff\x82\x00\x01\x02\
+    \x01\x05Votes\x01\xff\x88\x00\x01\x06Weight\x01\xff\x8a\x00\x00\x00\
+    \x1b\xff\x87\x02\x01\x01\x0c[][]*big.Int\x01\xff\x88\x00\x01\xff\x86\x00\x00\
+    \x0d\xff\x85\x02\x01\x02\xff\x86\x00\x01\xff\x84\x00\x00\
+    \x0a\xff\x83\x05\x01\x02\xff\x8c\x00\x00\x00\
+    \x0a\xff\x89\x05\x01\x02\xff\x8e\x00\x00\x00\
+    \x0b\xff\x82\x01\x01\x02\x02\x02\x02\x01\x02\x00";
+
+const STATE1: [&[u8]; 6] = [
+    b"01234567890123456789012345678900", // processID
+    b"01234567890123456789012345678901", // censusRoot
+    b"1234",                             // ballotMode
+    b"01234567890123456789012345678902", // encryptionKey
+    RESULTS_ADD,                         // resultsAdd
+    RESULTS_SUB,                         // resultsSub
+];
+
+const STATE1_ROOT: &str =
+    "16750619497458911427423466881209807301717692844273359695786822128194240222246";
+const STATE2_ROOT: &str =
+    "81134290496802997380210233330765495039509270148592539728056043846240103801670";
+const LEVELS_256_ROOT: &str =
+    "41261207203561506644474754617177867843065408341340544433344599122643601130473";
+
+fn state1_tree() -> Tree {
+    let mut tree = Tree::new(8);
+    for (key, value) in STATE1.iter().enumerate() {
+        tree.add(&[key as u8], value).unwrap();
+    }
+    tree
+}
+
+/// Applies the updates `main.go` makes between both versions of the state.
+fn state2_tree() -> Tree {
+    let mut tree = state1_tree();
+    for key in [1, 3] {
+        let mut value = STATE1[key].to_vec();
+        value[0] = 0x02;
+        tree.update(&[key as u8], &value).unwrap();
+    }
+    tree
+}
+
+fn config() -> TreeConfig {
+    TreeConfig::new(8, Blake3)
+}
+
+fn state_vector(version: u8, key: u8) -> String {
+    read_vector(&format!("regression/state{}merkleproof{}", version, key))
+}
+
+fn key_256(i: u64) -> Vec<u8> {
+    let mut key = vec![0u8; 32];
+    key[..8].copy_from_slice(&i.to_le_bytes());
+    key
+}
+
+/// A 256-level tree like the one of `TestProofWith256Levels`: the 6 state slots plus 3001
+/// leaves.
+fn tree_256() -> Tree {
+    let mut tree = Tree::new(256);
+    for (key, value) in STATE1.iter().enumerate() {
+        tree.add(&key_256(key as u64), value).unwrap();
+    }
+    for key in 100000..=103000 {
+        tree.add(&key_256(key), b"01234567890123456789012345678903")
+            .unwrap();
+    }
+    tree
+}
+
+#[test]
+fn state_vectors_verify() {
+    for (version, tree, root) in [
+        (1, state1_tree(), STATE1_ROOT),
+        (2, state2_tree(), STATE2_ROOT),
+    ] {
+        assert_eq!(hash_to_u256(&tree.root()).to_string(), root);
+        for key in 0..STATE1.len() as u8 {
+            let proof = parse(&state_vector(version, key)).unwrap();
+            assert_eq!(proof.value, tree.get(&[key]).unwrap());
+            assert_eq!(
+                hash_to_u256(&compute_root(&config(), &proof).unwrap()).to_string(),
+                root
+            );
+            verify_circom_proof(&config(), &proof).unwrap();
+        }
+    }
+}
+
+#[test]
+fn state_vectors_round_trip() {
+    for (version, tree) in [(1, state1_tree()), (2, state2_tree())] {
+        for key in 0..STATE1.len() as u8 {
+            let proof = tree.generate_circom_verifier_proof(&[key]).unwrap();
+            assert_eq!(to_json(proof), state_vector(version, key).trim_end());
+        }
+    }
+}
+
+#[test]
+fn levels_256() {
+    let config = TreeConfig::new(256, Blake3);
+    let tree = tree_256();
+    assert_eq!(hash_to_u256(&tree.root()).to_string(), LEVELS_256_ROOT);
+    for (key, name) in [
+        (103000, "merkleproof256"),
+        (99999, "merkleproof256_nonexistence"),
+    ] {
+        let json = read_vector(&format!("regression/{}", name));
+        let proof = parse(&json).unwrap();
+        verify_circom_proof(&config, &proof).unwrap();
+        assert_eq!(
+            hash_to_u256(&compute_root(&config, &proof).unwrap()).to_string(),
+            LEVELS_256_ROOT
+        );
+
+        let proof = tree.generate_circom_verifier_proof(&key_256(key)).unwrap();
+        assert_eq!(to_json(proof), json);
+    }
+}
+
+#[test]
+fn state_batch() {
+    let read = |version, keys: &[u8]| -> Vec<MerkleProof> {
+        keys.iter()
+            .map(|&key| parse(&state_vector(version, key)).unwrap())
+            .collect()
+    };
+
+    // main.go checks the keys it updated, along with the unchanged 0 and 2
+    check_proof_batch(&config(), &read(1, &[0, 1, 2, 3]), &read(2, &[0, 1, 2, 3])).unwrap();
+    assert_eq!(
+        check_proof_batch(&config(), &read(1, &[0, 1, 2]), &read(2, &[0, 1, 2])),
+        Err(VerifyError::UnexpectedChange)
+    );
+}
+
+#[test]
+fn batch_keeps_leaf_positions() {
+    let mut tree = Tree::new(4);
+    for key in [0u8, 1, 2, 4] {
+        tree.add(&[key], &[key + 10]).unwrap();
+    }
+    let old = tree.generate_circom_verifier_proof(&[0]).unwrap();
+
+    // the leaves of 1 and 2 swapped: the same hashes, in the wrong places
+    let mut new = old.clone();
+    new.siblings.swap(0, 1);
+    new.root = compute_root(&tree.config(), &new).unwrap();
+    assert_eq!(
+        check_proof_batch(&tree.config(), std::slice::from_ref(&old), &[new]),
+        Err(VerifyError::UnexpectedChange)
+    );
+
+    // deleting 0 pulls the lone leaf of 4 up along its path, which is fine
+    tree.delete(&[0]).unwrap();
+    let new = tree.generate_circom_verifier_proof(&[0]).unwrap();
+    check_proof_batch(&tree.config(), &[old], &[new]).unwrap();
+}
+
+#[test]
+fn all_slots_of_both_states() {
+    let proofs: Vec<MerkleProof> = [1, 2]
+        .into_iter()
+        .flat_map(|version| (0..STATE1.len() as u8).map(move |key| (version, key)))
+        .map(|(version, key)| parse(&state_vector(version, key)).unwrap())
+        .collect();
+    for proof in &proofs {
+        verify_circom_proof(&config(), proof).unwrap();
+    }
+
+    let bytes = OutputMode::RootKey.encode_all(&config(), &proofs);
+    let pairs = Vec::<RootKeyPublicValuesStruct>::abi_decode(&bytes, true).unwrap();
+    assert_eq!(pairs.len(), 2 * STATE1.len());
+    for (i, pair) in pairs.iter().enumerate() {
+        let root = if i < STATE1.len() {
+            STATE1_ROOT
+        } else {
+            STATE2_ROOT
+        };
+        assert_eq!(pair.root.to_string(), root);
+        assert_eq!(pair.key, hash_to_u256(&proofs[i].key));
+        assert!(!pair.fnc);
+    }
+}
+
+#[test]
+fn public_values_of_other_statements() {
+    let state1 = parse(&state_vector(1, 1)).unwrap();
+    let state2 = parse(&state_vector(2, 1)).unwrap();
+    let absent = state1_tree().generate_circom_verifier_proof(&[6]).unwrap();
+    let proofs = [state1.clone(), absent.clone()];
+    let expected: Vec<Statement> = proofs.iter().map(Statement::from).collect();
+
+    let mut other_root = expected.clone();
+    other_root[0].root = state2.root;
+    let mut other_value = expected.clone();
+    other_value[0].value = Some(state2.value.clone());
+    let mut other_key = expected.clone();
+    other_key[0].key = parse(&state_vector(1, 3)).unwrap().key;
+    // the old leaf of a non-inclusion proof is not in the tree under the key proven
+    let mut included = expected.clone();
+    included[1].value = Some(absent.old_value.clone());
+
+    let other_config = TreeConfig {
+        max_levels: 255,
+        ..config()
+    };
+
+    let mismatch = |index| Err(PublicValuesError::Mismatch { index });
+    for (mode, value_hidden, key_hidden, fnc_hidden) in [
+        (OutputMode::Full, false, false, false),
+        (OutputMode::RootKey, true, false, false),
+        (OutputMode::RootValueHash, false, true, false),
+        (OutputMode::Root, true, true, true),
+    ] {
+        let bytes = mode.encode_all(&config(), &proofs);
+        assert_eq!(mode.check_all(&bytes, &config(), &expected), Ok(()));
+        assert_eq!(
+            mode.check_all(&bytes, &other_config, &expected),
+            mismatch(0)
+        );
+        assert_eq!(
+            mode.check_all(&bytes, &config(), &expected[..1]),
+            Err(PublicValuesError::Count {
+                expected: 1,
+                committed: 2
+            })
+        );
+        assert_eq!(mode.check_all(&bytes, &config(), &other_root), mismatch(0));
+        let hidden = |hidden, index| if hidden { Ok(()) } else { mismatch(index) };
+        assert_eq!(
+            mode.check_all(&bytes, &config(), &other_value),
+            hidden(value_hidden, 0)
+        );
+        assert_eq!(
+            mode.check_all(&bytes, &config(), &other_key),
+            hidden(key_hidden, 0)
+        );
+        assert_eq!(
+            mode.check_all(&bytes, &config(), &included),
+            hidden(fnc_hidden, 1)
+        );
+        assert_eq!(
+            mode.check_all(&bytes[1..], &config(), &expected),
+            Err(PublicValuesError::Decode)
+        );
+    }
+
+    let to_biguint = |bytes: &[u8]| BigUint::from_bytes_le(bytes);
+    let statement = Statement::from_biguints(
+        &to_biguint(&state1.root),
+        &to_biguint(&state1.key),
+        Some(&to_biguint(&state1.value)),
+        None,
+        1,
+    );
+    assert_eq!(statement, Ok(expected[0].clone()));
+}
+
+#[test]
+fn non_inclusion_hides_the_old_leaf() {
+    // the path of 6 ends in the leaf of 2, i.e. `ballotMode`
+    let absent = state1_tree().generate_circom_verifier_proof(&[6]).unwrap();
+    assert!(absent.fnc && !absent.is_old_0);
+    let expected = [Statement::from(&absent)];
+    for mode in [
+        OutputMode::Full,
+        OutputMode::RootKey,
+        OutputMode::RootValueHash,
+        OutputMode::Root,
+    ] {
+        let bytes = mode.encode(&config(), &absent);
+        let old_key = hash_to_u256(&absent.old_key).to_be_bytes::<32>();
+        let hidden = |part: &[u8]| !bytes.windows(part.len()).any(|w| w == part);
+        assert!(hidden(&absent.old_value), "{:?}", mode);
+        assert!(
+            hidden(keccak256(&absent.old_value).as_slice()),
+            "{:?}",
+            mode
+        );
+        assert!(hidden(&old_key), "{:?}", mode);
+
+        let bytes = mode.encode_all(&config(), std::slice::from_ref(&absent));
+        assert_eq!(mode.check_all(&bytes, &config(), &expected), Ok(()));
+    }
+
+    let full = PublicValuesStruct::abi_decode(&OutputMode::Full.encode(&config(), &absent), true);
+    assert!(full.unwrap().proof.value.is_empty());
+    let bytes = OutputMode::RootValueHash.encode(&config(), &absent);
+    let value_hash = RootValueHashPublicValuesStruct::abi_decode(&bytes, true).unwrap();
+    assert_eq!(value_hash.valueHash, keccak256([]));
+}
+
+#[test]
+fn public_values_commit_the_config() {
+    // with one more key byte, the first byte of the value moves into the key and the leaf
+    // hash stays the same, so the proof only holds for the config it was verified against
+    let mut tree = Tree::new(8);
+    tree.add(&[1], b"1234").unwrap();
+    let mut forged = tree.generate_circom_verifier_proof(&[1]).unwrap();
+    forged.key[1] = b'1';
+    forged.value = b"234".to_vec();
+    let forged_config = TreeConfig {
+        key_len: 2,
+        ..tree.config()
+    };
+    verify_circom_proof(&forged_config, &forged).unwrap();
+    assert!(verify_circom_proof(&tree.config(), &forged).is_err());
+
+    let expected = [Statement::from(&forged)];
+    for mode in [
+        OutputMode::Full,
+        OutputMode::RootKey,
+        OutputMode::RootValueHash,
+        OutputMode::Root,
+    ] {
+        let bytes = mode.encode_all(&forged_config, std::slice::from_ref(&forged));
+        assert_eq!(mode.check_all(&bytes, &forged_config, &expected), Ok(()));
+        assert_eq!(
+            mode.check_all(&bytes, &tree.config(), &expected),
+            Err(PublicValuesError::Mismatch { index: 0 })
+        );
+        #[cfg(feature = "sha256")]
+        assert_eq!(
+            mode.check_all(
+                &bytes,
+                &TreeConfig::new(8, smtverifier::hasher::Sha256),
+                &expected
+            ),
+            Err(PublicValuesError::Mismatch { index: 0 })
+        );
+    }
+}
+
+#[test]
+fn state_transitions() {
+    let mut tree = state1_tree();
+    let state2 = state2_tree();
+    let mut transitions = Vec::new();
+    for key in [1, 3] {
+        let value = state2.get(&[key]).unwrap();
+        transitions.push(tree.update_with_proof(&[key], &value).unwrap());
+    }
+    transitions.push(tree.add_with_proof(&[6], b"votes 0 0\x00").unwrap());
+    transitions.push(tree.delete_with_proof(&[6]).unwrap());
+    assert_eq!(tree.root(), state2.root());
+
+    let root = apply_transitions(&config(), &state1_tree().root(), &transitions).unwrap();
+    assert_eq!(hash_to_u256(&root).to_string(), STATE2_ROOT);
+
+    // the transitions only apply in the order they were made
+    transitions.swap(0, 1);
+    assert!(matches!(
+        apply_transitions(&config(), &state1_tree().root(), &transitions),
+        Err(VerifyError::RootMismatch { .. })
+    ));
+}
+
+#[test]
+fn values_are_not_truncated_to_the_key() {
+    let mut proof = parse(&state_vector(1, 0)).unwrap();
+    proof.value.truncate(1);
+    assert!(matches!(
+        verify_circom_proof(&config(), &proof),
+        Err(VerifyError::RootMismatch { .. })
+    ));
+}
+
+#[test]
+fn gob_values_need_the_value_length() {
+    for key in [4, 5] {
+        // a gob stream ends with the zero byte closing its last struct, which the decimal
+        // `value` of arbo's JSON drops: `go run .` writes no `valueLen`, so it's added by hand
+        let value = STATE1[key];
+        assert_eq!(value.last(), Some(&0));
+        let json = state_vector(1, key as u8);
+        let value_len = format!(r#""valueLen":{}"#, value.len());
+        assert!(json.contains(&value_len));
+
+        let arbo = json.replace(&format!(",{}", value_len), "");
+        let proof = parse(&arbo).unwrap();
+        assert_eq!(proof.value, value[..value.len() - 1]);
+        assert!(matches!(
+            verify_circom_proof(&config(), &proof),
+            Err(VerifyError::RootMismatch { .. })
+        ));
+        let proof = parse(&json).unwrap();
+        assert_eq!(proof.value, value);
+        verify_circom_proof(&config(), &proof).unwrap();
+
+        let short = format!(r#""valueLen":{}"#, value.len() - 2);
+        let res = parse(&json.replace(&value_len, &short));
+        assert_eq!(res.unwrap_err(), VerifyError::Overflow);
+
+        // the same goes for the value of an expected statement
+        let expected = Statement::from(&proof);
+        let statement = |value_len| {
+            Statement::from_biguints(
+                &BigUint::from_bytes_le(&proof.root),
+                &BigUint::from(key),
+                Some(&BigUint::from_bytes_le(value)),
+                value_len,
+                1,
+            )
+        };
+        assert_eq!(statement(Some(value.len())), Ok(expected.clone()));
+        assert_ne!(statement(None), Ok(expected));
+        assert_eq!(statement(Some(value.len() - 2)), Err(VerifyError::Overflow));
+    }
+
+    // every message of the gob stream is prefixed with its length
+    for mut stream in [RESULTS_ADD, RESULTS_SUB] {
+        let mut messages = 0;
+        while let Some((&len, rest)) = stream.split_first() {
+            stream = &rest[len as usize..];
+            messages += 1;
+        }
+        assert_eq!(messages, 6);
+    }
+}
+
+#[test]
+fn roots_keys_and_siblings_overflow() {
+    let json = state_vector(1, 4);
+    let proof = parse(&json).unwrap();
+    let root = hash_to_u256(&proof.root).to_string();
+    let wide = (BigUint::from(1u8) << 256usize).to_string();
+    let res = parse(&json.replace(&root, &wide));
+    assert_eq!(res.unwrap_err(), VerifyError::Overflow);
+    assert!(VerifyError::Overflow.to_string().contains("root"));
+}
+
+#[test]
+fn non_inclusion_of_long_old_values() {
+    let tree = state1_tree();
+    for key in STATE1.len() as u8..=u8::MAX {
+        let proof = tree.generate_circom_verifier_proof(&[key]).unwrap();
+        let parsed = parse(&to_json(proof.clone())).unwrap();
+        assert_eq!(parsed.old_value, proof.old_value);
+        verify_circom_proof(&tree.config(), &parsed).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+//! Aggregates proofs of the `arbo` program into a single proof.
+//!
+//! Reads the verifying key of the `arbo` program and the public values of each proof, and
+//! verifies the proofs, which the prover passes along with the stdin. Commits the verifying
+//! key and all the public values, in the same order.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use alloy_sol_types::SolType;
+use sha2::{Digest, Sha256};
+use smtverifier::{vkey_to_b256, AggregationPublicValuesStruct};
+
+fn main() {
+    let vkey = sp1_zkvm::io::read::<[u32; 8]>();
+    let public_values = sp1_zkvm::io::read::<Vec<Vec<u8>>>();
+
+    // The proofs are read in the same order they were written to the stdin, and each one is
+    // bound to its public values through their digest.
+    for values in &public_values {
+        let digest: [u8; 32] = Sha256::digest(values).into();
+        sp1_zkvm::lib::verify::verify_sp1_proof(&vkey, &digest);
+    }
+
+    let bytes = AggregationPublicValuesStruct::abi_encode(&AggregationPublicValuesStruct {
+        vkey: vkey_to_b256(&vkey),
+        publicValues: public_values.into_iter().map(Into::into).collect(),
+    });
+    sp1_zkvm::io::commit_slice(&bytes);
+}
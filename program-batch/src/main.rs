@@ -0,0 +1,26 @@
+//! Proves that two trees only differ in a given set of keys, as arbo's `CheckProofBatch`.
+//!
+//! Reads the tree configuration and the old and new proofs for the same keys, and commits
+//! the configuration, both roots and the keys.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use alloy_sol_types::SolType;
+use smtverifier::{hash_to_u256, MerkleProof, ProofBatchPublicValuesStruct, TreeConfig};
+
+fn main() {
+    let config = sp1_zkvm::io::read::<TreeConfig>();
+    let old_proofs = sp1_zkvm::io::read::<Vec<MerkleProof>>();
+    let new_proofs = sp1_zkvm::io::read::<Vec<MerkleProof>>();
+
+    smtverifier::check_proof_batch(&config, &old_proofs, &new_proofs)
+        .unwrap_or_else(|e| panic!("proof batch verification failed: {}", e));
+
+    let bytes = ProofBatchPublicValuesStruct::abi_encode(&ProofBatchPublicValuesStruct {
+        config: (&config).into(),
+        oldRoot: hash_to_u256(&old_proofs[0].root),
+        newRoot: hash_to_u256(&new_proofs[0].root),
+        keys: old_proofs.iter().map(|p| hash_to_u256(&p.key)).collect(),
+    });
+    sp1_zkvm::io::commit_slice(&bytes);
+}
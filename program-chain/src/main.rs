@@ -0,0 +1,57 @@
+//! Proves one step of the history of a tree, extending the proof of the previous step.
+//!
+//! Reads its own verifying key, the tree configuration, the genesis root, the public values
+//! of the previous step (none for the first one) and a batch of transitions. The previous
+//! proof, which the prover passes along with the stdin, is verified recursively, so the
+//! committed public values attest to every step from the genesis root.
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolType;
+use sha2::{Digest, Sha256};
+use smtverifier::{
+    hash_to_u256, vkey_to_b256, ChainPublicValuesStruct, Hash, TransitionProof, TreeConfig,
+    TreeConfigSol,
+};
+
+fn main() {
+    let vkey = sp1_zkvm::io::read::<[u32; 8]>();
+    let config = sp1_zkvm::io::read::<TreeConfig>();
+    let genesis = sp1_zkvm::io::read::<Hash>();
+    let previous = sp1_zkvm::io::read::<Option<Vec<u8>>>();
+    let transitions = sp1_zkvm::io::read::<Vec<TransitionProof>>();
+
+    let mut values = ChainPublicValuesStruct {
+        vkey: vkey_to_b256(&vkey),
+        config: TreeConfigSol::from(&config),
+        genesisRoot: hash_to_u256(&genesis),
+        root: hash_to_u256(&genesis),
+        steps: U256::ZERO,
+    };
+
+    if let Some(previous) = previous {
+        let digest: [u8; 32] = Sha256::digest(&previous).into();
+        sp1_zkvm::lib::verify::verify_sp1_proof(&vkey, &digest);
+
+        // the previous step must come from this same program, for the same tree and history
+        let previous = ChainPublicValuesStruct::abi_decode(&previous, true)
+            .unwrap_or_else(|e| panic!("invalid previous public values: {}", e));
+        assert_eq!(previous.vkey, values.vkey, "previous step has another vkey");
+        assert_eq!(previous.config, values.config, "tree config changed");
+        assert_eq!(
+            previous.genesisRoot, values.genesisRoot,
+            "genesis root changed"
+        );
+        values.root = previous.root;
+        values.steps = previous.steps;
+    }
+
+    let root: Hash = values.root.to_le_bytes();
+    let root = smtverifier::apply_transitions(&config, &root, &transitions)
+        .unwrap_or_else(|e| panic!("transition verification failed: {}", e));
+    values.root = hash_to_u256(&root);
+    values.steps += U256::from(1);
+
+    sp1_zkvm::io::commit_slice(&ChainPublicValuesStruct::abi_encode(&values));
+}
@@ -5,24 +5,26 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use smtverifier::MerkleProof;
+use smtverifier::{MerkleProof, OutputMode, TreeConfig};
 
 fn main() {
     println!("start");
 
-    let proof = sp1_zkvm::io::read::<MerkleProof>();
+    let config = sp1_zkvm::io::read::<TreeConfig>();
+    let mode = sp1_zkvm::io::read::<OutputMode>();
+    let proofs = sp1_zkvm::io::read::<Vec<MerkleProof>>();
 
-    for n in 1..10 {
-        println!("dummy loop {}", n);
-        smtverifier::verify(
-            &(proof.root),
-            &(proof.key),
-            &(proof.value),
-            proof.siblings.clone(),
-        );
+    // Each proof is checked against its own root, so the proofs may come from different
+    // trees (or different versions of the same tree) as long as they share the config.
+    for (i, proof) in proofs.iter().enumerate() {
+        smtverifier::verify_circom_proof(&config, proof)
+            .unwrap_or_else(|e| panic!("merkle proof {} verification failed: {}", i, e));
     }
 
-    smtverifier::verify(&(proof.root), &(proof.key), &(proof.value), proof.siblings);
+    // Commit the verified proofs, or the parts of them selected by the output mode, as an
+    // array in the order they were read, so that they can be read from the public values.
+    // Each one carries the config, which comes from the untrusted stdin.
+    sp1_zkvm::io::commit_slice(&mode.encode_all(&config, &proofs));
 
     println!("done");
 }
@@ -1,5 +1,26 @@
-use sp1_helper::build_program_with_args;
+use sp1_build::{build_program_with_args, BuildArgs};
 
 fn main() {
-    build_program_with_args("../program", Default::default())
+    build_program_with_args("../program", Default::default());
+    build_program_with_args(
+        "../program-batch",
+        BuildArgs {
+            elf_name: "riscv32im-succinct-zkvm-batch-elf".to_string(),
+            ..Default::default()
+        },
+    );
+    build_program_with_args(
+        "../program-aggregate",
+        BuildArgs {
+            elf_name: "riscv32im-succinct-zkvm-aggregate-elf".to_string(),
+            ..Default::default()
+        },
+    );
+    build_program_with_args(
+        "../program-chain",
+        BuildArgs {
+            elf_name: "riscv32im-succinct-zkvm-chain-elf".to_string(),
+            ..Default::default()
+        },
+    );
 }
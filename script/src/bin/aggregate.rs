@@ -0,0 +1,127 @@
+//! Proves each Merkle proof file with the `arbo` program, then aggregates all those proofs
+//! into a single one with the aggregation program.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin aggregate -- --execute \
+//!     -f state1merkleproof0.json -f state1merkleproof1.json -f state2merkleproof0.json
+//! ```
+
+use alloy_sol_types::SolType;
+use arbo_sandbox_script::{check_proofs, read_merkleproof_from_file, TreeArgs};
+use clap::Parser;
+use smtverifier::{vkey_to_b256, AggregationPublicValuesStruct, MerkleProof, OutputMode};
+use sp1_sdk::{HashableKey, ProverClient, SP1Proof, SP1Stdin};
+use std::time::Instant;
+
+/// The ELF of the program that verifies Merkle proofs.
+pub const ARBO_ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-elf");
+
+/// The ELF of the program that aggregates proofs of [`ARBO_ELF`].
+pub const AGGREGATE_ELF: &[u8] =
+    include_bytes!("../../../elf/riscv32im-succinct-zkvm-aggregate-elf");
+
+/// The arguments for the command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(long)]
+    execute: bool,
+
+    #[clap(long)]
+    prove: bool,
+
+    /// Proof files, each one proven separately by the `arbo` program and then aggregated.
+    #[clap(short, required = true)]
+    f: Vec<String>,
+
+    #[clap(flatten)]
+    tree: TreeArgs,
+
+    /// Parts of each proof committed as public values by the `arbo` program.
+    #[clap(long, default_value = "root-key")]
+    output: OutputMode,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse the command line arguments.
+    let args = Args::parse();
+
+    if args.execute == args.prove {
+        eprintln!("Error: You must specify either --execute or --prove");
+        std::process::exit(1);
+    }
+
+    let proofs: Vec<MerkleProof> = args.f.iter().map(read_merkleproof_from_file).collect();
+    let config = args.tree.config(proofs[0].siblings.len());
+    check_proofs(&config, &args.f, &proofs);
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+    let (arbo_pk, arbo_vk) = client.setup(ARBO_ELF);
+
+    // First stage: prove each Merkle proof on its own. The proofs have to be compressed to
+    // be verified inside the aggregation program.
+    let mut public_values = Vec::new();
+    let mut inner_proofs = Vec::new();
+    for (f, proof) in args.f.iter().zip(proofs) {
+        let mut arbo_stdin = SP1Stdin::new();
+        arbo_stdin.write(&config);
+        arbo_stdin.write(&args.output);
+        arbo_stdin.write(&vec![proof]);
+
+        let start_time = Instant::now();
+        let inner = client
+            .prove(&arbo_pk, arbo_stdin)
+            .compressed()
+            .run()
+            .expect("failed to generate proof");
+        println!("Proved {} in {:?}", f, start_time.elapsed());
+
+        public_values.push(inner.public_values.to_vec());
+        inner_proofs.push(inner.proof);
+    }
+
+    // Second stage: aggregate them, passing each proof along with the public values it
+    // has to match.
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&arbo_vk.hash_u32());
+    stdin.write(&public_values);
+    for proof in inner_proofs {
+        let SP1Proof::Compressed(proof) = proof else {
+            panic!("expected a compressed proof")
+        };
+        stdin.write_proof(*proof, arbo_vk.vk.clone());
+    }
+
+    let output = if args.execute {
+        let start_time = Instant::now();
+        let (output, report) = client.execute(AGGREGATE_ELF, stdin).run().unwrap();
+        println!("Program executed successfully.");
+        println!("Time elapsed: {:?}", start_time.elapsed());
+        println!("Number of cycles: {}", report.total_instruction_count());
+        output
+    } else {
+        let (pk, vk) = client.setup(AGGREGATE_ELF);
+        let proof = client
+            .prove(&pk, stdin)
+            .groth16()
+            .run()
+            .expect("failed to generate proof");
+        client.verify(&proof, &vk).expect("failed to verify proof");
+        println!("Successfully generated and verified the aggregated proof!");
+        println!("aggregation vkey: {}", vk.bytes32());
+        proof.public_values
+    };
+
+    let decoded = AggregationPublicValuesStruct::abi_decode(output.as_slice(), true)
+        .expect("failed to decode public values");
+    assert_eq!(decoded.vkey, vkey_to_b256(&arbo_vk.hash_u32()));
+    println!("arbo vkey: {}", decoded.vkey);
+    for (i, values) in decoded.publicValues.iter().enumerate() {
+        println!("[{}] public values: {}", i, values);
+    }
+}
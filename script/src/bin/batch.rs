@@ -0,0 +1,104 @@
+//! Proves that two versions of a tree only differ in a given set of keys, from the proofs
+//! of those keys in both versions (e.g. the `state1merkleproof*.json` and
+//! `state2merkleproof*.json` files written by `go run .`).
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin batch -- --execute \
+//!     --old state1merkleproof0.json --old state1merkleproof1.json \
+//!     --new state2merkleproof0.json --new state2merkleproof1.json
+//! ```
+
+use alloy_sol_types::SolType;
+use arbo_sandbox_script::{read_merkleproof_from_file, TreeArgs};
+use clap::Parser;
+use smtverifier::{MerkleProof, ProofBatchPublicValuesStruct};
+use sp1_sdk::{ProverClient, SP1Stdin};
+use std::time::Instant;
+
+/// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
+pub const BATCH_ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-batch-elf");
+
+/// The arguments for the command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(long)]
+    execute: bool,
+
+    #[clap(long)]
+    prove: bool,
+
+    /// Proofs of the keys in the old tree.
+    #[clap(long, required = true)]
+    old: Vec<String>,
+
+    /// Proofs of the same keys, in the same order, in the new tree.
+    #[clap(long, required = true)]
+    new: Vec<String>,
+
+    #[clap(flatten)]
+    tree: TreeArgs,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse the command line arguments.
+    let args = Args::parse();
+
+    if args.execute == args.prove {
+        eprintln!("Error: You must specify either --execute or --prove");
+        std::process::exit(1);
+    }
+
+    let read_all = |paths: &[String]| -> Vec<MerkleProof> {
+        paths.iter().map(read_merkleproof_from_file).collect()
+    };
+    let old_proofs = read_all(&args.old);
+    let new_proofs = read_all(&args.new);
+    let config = args.tree.config(old_proofs[0].siblings.len());
+
+    // Check the batch natively first, so that an invalid batch is reported with its cause.
+    if let Err(e) = smtverifier::check_proof_batch(&config, &old_proofs, &new_proofs) {
+        eprintln!("Error: invalid proof batch: {}", e);
+        std::process::exit(1);
+    }
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+
+    // Setup the inputs.
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&config);
+    stdin.write(&old_proofs);
+    stdin.write(&new_proofs);
+
+    let public_values = if args.execute {
+        let start_time = Instant::now();
+        let (output, report) = client.execute(BATCH_ELF, stdin).run().unwrap();
+        println!("Program executed successfully.");
+        println!("Time elapsed: {:?}", start_time.elapsed());
+        println!("Number of cycles: {}", report.total_instruction_count());
+        output
+    } else {
+        let (pk, vk) = client.setup(BATCH_ELF);
+        let proof = client
+            .prove(&pk, stdin)
+            .run()
+            .expect("failed to generate proof");
+        client.verify(&proof, &vk).expect("failed to verify proof");
+        println!("Successfully generated and verified proof!");
+        proof.public_values
+    };
+
+    let decoded = ProofBatchPublicValuesStruct::abi_decode(public_values.as_slice(), true)
+        .expect("failed to decode public values");
+    print!("{}", decoded.config);
+    println!("old root: {}", decoded.oldRoot);
+    println!("new root: {}", decoded.newRoot);
+    for key in decoded.keys {
+        println!("key allowed to change: {}", key);
+    }
+}
@@ -0,0 +1,169 @@
+//! Proves the history of a tree across several versions, one step per version, each step
+//! extending the proof of the previous one.
+//!
+//! Every version is given as the proofs of all its leaves (e.g. the `state1merkleproof*.json`
+//! and `state2merkleproof*.json` files written by `go run .`), from which the tree is rebuilt
+//! to generate the transitions between consecutive versions.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin chain -- \
+//!     --state state1merkleproof0.json,state1merkleproof1.json,... \
+//!     --state state2merkleproof0.json,state2merkleproof1.json,...
+//! ```
+
+use alloy_sol_types::SolType;
+use arbo_sandbox_script::{read_merkleproof_from_file, TreeArgs};
+use clap::Parser;
+use smtverifier::{
+    hash_to_u256, vkey_to_b256, Blake3, ChainPublicValuesStruct, MerkleProof, TransitionProof,
+    Tree, TreeConfig, TreeConfigSol,
+};
+use sp1_sdk::{HashableKey, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1Stdin};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
+pub const CHAIN_ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-chain-elf");
+
+/// The arguments for the command.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Proofs of every leaf of a version of the tree, comma separated. Given once per
+    /// version, from the genesis one to the last one.
+    #[clap(long, required = true)]
+    state: Vec<String>,
+
+    #[clap(flatten)]
+    tree: TreeArgs,
+}
+
+/// Rebuilds the tree from the inclusion proofs of all its leaves, checking that they are
+/// all against the root of the rebuilt tree.
+fn rebuild_tree(config: TreeConfig<Blake3>, proofs: &[MerkleProof]) -> Tree {
+    let mut tree = Tree::with_config(config);
+    for proof in proofs.iter().filter(|p| !p.fnc) {
+        tree.add(&proof.key[..tree.key_len()], &proof.value)
+            .expect("failed to add leaf");
+    }
+    if proofs.iter().any(|p| p.root != tree.root()) {
+        eprintln!("Error: the proofs of a state don't cover all of its leaves");
+        std::process::exit(1);
+    }
+    tree
+}
+
+/// Returns the transitions that turn `tree` into `next`, applying them to `tree`.
+fn transitions(
+    tree: &mut Tree,
+    next: &Tree,
+    old: &[MerkleProof],
+    new: &[MerkleProof],
+) -> Vec<TransitionProof> {
+    let key_len = tree.key_len();
+    let leaves = |proofs: &[MerkleProof]| -> BTreeMap<Vec<u8>, Vec<u8>> {
+        proofs
+            .iter()
+            .filter(|p| !p.fnc)
+            .map(|p| (p.key[..key_len].to_vec(), p.value.clone()))
+            .collect()
+    };
+    let (old, new) = (leaves(old), leaves(new));
+
+    let mut transitions = Vec::new();
+    for key in old.keys().filter(|k| !new.contains_key(*k)) {
+        transitions.push(tree.delete_with_proof(key).expect("failed to delete leaf"));
+    }
+    for (key, value) in &new {
+        match old.get(key) {
+            Some(old_value) if old_value == value => {}
+            Some(_) => transitions.push(
+                tree.update_with_proof(key, value)
+                    .expect("failed to update leaf"),
+            ),
+            None => transitions.push(tree.add_with_proof(key, value).expect("failed to add leaf")),
+        }
+    }
+    assert_eq!(tree.root(), next.root());
+    transitions
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Parse the command line arguments.
+    let args = Args::parse();
+
+    let states: Vec<Vec<MerkleProof>> = args
+        .state
+        .iter()
+        .map(|files| files.split(',').map(read_merkleproof_from_file).collect())
+        .collect();
+
+    if states.len() < 2 {
+        eprintln!("Error: You must give at least two states");
+        std::process::exit(1);
+    }
+
+    let config = args.tree.config(states[0][0].siblings.len());
+    let trees: Vec<Tree> = states.iter().map(|p| rebuild_tree(config, p)).collect();
+    let genesis = trees[0].root();
+
+    // Setup the prover client.
+    let client = ProverClient::new();
+    let (pk, vk) = client.setup(CHAIN_ELF);
+
+    // Each step is proven compressed, so that the next one can verify it.
+    let mut tree = trees[0].clone();
+    let mut previous: Option<SP1ProofWithPublicValues> = None;
+    for step in 1..trees.len() {
+        let transitions = transitions(&mut tree, &trees[step], &states[step - 1], &states[step]);
+
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&vk.hash_u32());
+        stdin.write(&config);
+        stdin.write(&genesis);
+        stdin.write(&previous.as_ref().map(|p| p.public_values.to_vec()));
+        stdin.write(&transitions);
+        if let Some(SP1Proof::Compressed(proof)) = previous.take().map(|p| p.proof) {
+            stdin.write_proof(*proof, vk.vk.clone());
+        }
+
+        let start_time = Instant::now();
+        let proof = client
+            .prove(&pk, stdin)
+            .compressed()
+            .run()
+            .expect("failed to generate proof");
+        println!(
+            "Proved step {} ({} transitions) in {:?}",
+            step,
+            transitions.len(),
+            start_time.elapsed()
+        );
+
+        previous = Some(proof);
+    }
+
+    let proof = previous.expect("at least one step was proven");
+    client.verify(&proof, &vk).expect("failed to verify proof");
+    println!("Successfully verified proof!");
+
+    // The program takes its vkey from stdin, so a proof is only trusted once the vkey it
+    // commits is the one of this program.
+    let decoded = ChainPublicValuesStruct::abi_decode(proof.public_values.as_slice(), true)
+        .expect("failed to decode public values");
+    assert_eq!(decoded.vkey, vkey_to_b256(&vk.hash_u32()));
+    assert_eq!(decoded.genesisRoot, hash_to_u256(&genesis));
+    assert_eq!(decoded.config, TreeConfigSol::from(&config));
+    // An external verifier pins both: the proof must be for this vkey, and its public
+    // values must commit it, as the program can't tell its own vkey apart from another.
+    println!("vkey: {}", vk.bytes32());
+    println!("committed vkey: {}", decoded.vkey);
+    print!("{}", decoded.config);
+    println!("genesis root: {}", decoded.genesisRoot);
+    println!("root: {}", decoded.root);
+    println!("steps: {}", decoded.steps);
+}
@@ -1,5 +1,5 @@
-//! An end-to-end example of using the SP1 SDK to generate a proof of a program that can have an
-//! EVM-Compatible proof generated which can be verified on-chain.
+//! An end-to-end example of using the SP1 SDK to generate an EVM-compatible proof of a
+//! Merkle proof, which can be verified on-chain.
 //!
 //! You can run this script using the following command:
 //! ```shell
@@ -7,43 +7,48 @@
 //! ```
 //! or
 //! ```shell
-//! RUST_LOG=info cargo run --release --bin evm -- --system plonk
+//! RUST_LOG=info cargo run --release --bin evm -- --system plonk -f merkleproof.json
 //! ```
 
-// use alloy_sol_types::SolType;
-use clap::{Parser, ValueEnum};
+use alloy_sol_types::{sol_data::Array, SolType};
+use arbo_sandbox_script::{check_proofs, read_merkleproof_from_file, TreeArgs};
+use clap::Parser;
+use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
-use smtverifier::{MerkleProof, MerkleProofFromFile};
+use smtverifier::{OutputMode, ProofSystem, PublicValuesStruct};
 use sp1_sdk::{HashableKey, ProverClient, SP1ProofWithPublicValues, SP1Stdin, SP1VerifyingKey};
 use std::path::PathBuf;
 
 /// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
-pub const FIBONACCI_ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-elf");
+pub const ARBO_ELF: &[u8] = include_bytes!("../../../elf/riscv32im-succinct-zkvm-elf");
 
 /// The arguments for the EVM command.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct EVMArgs {
-    #[clap(long, default_value = "20")]
-    n: u32,
-    #[clap(long, value_enum, default_value = "groth16")]
-    system: ProofSystem,
-}
+    #[clap(short, default_value = "merkleproof.json")]
+    f: String,
+
+    #[clap(flatten)]
+    tree: TreeArgs,
 
-/// Enum representing the available proof systems
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
-enum ProofSystem {
-    Plonk,
-    Groth16,
+    /// Proof system to wrap the proof with: groth16 or plonk.
+    #[clap(long, default_value = "groth16")]
+    system: ProofSystem,
 }
 
 /// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
+///
+/// The root, key and value are decimal numbers like in the proof files, so that they can be
+/// passed as they are to `--verify --root --key --value --value-len`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct SP1FibonacciProofFixture {
-    a: u32,
-    b: u32,
-    n: u32,
+struct SP1MerkleProofFixture {
+    root: String,
+    key: String,
+    value: String,
+    value_len: usize,
+    fnc: bool,
     vkey: String,
     public_values: String,
     proof: String,
@@ -56,17 +61,24 @@ fn main() {
     // Parse the command line arguments.
     let args = EVMArgs::parse();
 
+    let proof = read_merkleproof_from_file(&args.f);
+    let config = args.tree.config(proof.siblings.len());
+    check_proofs(&config, &[&args.f], std::slice::from_ref(&proof));
+
     // Setup the prover client.
     let client = ProverClient::new();
 
     // Setup the program.
-    let (pk, vk) = client.setup(FIBONACCI_ELF);
+    let (pk, vk) = client.setup(ARBO_ELF);
 
-    // Setup the inputs.
+    // Setup the inputs. The whole proof is committed, so that the contract can read the
+    // root, key and value from the public values.
     let mut stdin = SP1Stdin::new();
-    stdin.write(&args.n);
+    stdin.write(&config);
+    stdin.write(&OutputMode::Full);
+    stdin.write(&vec![proof]);
 
-    println!("n: {}", args.n);
+    println!("Merkle proof: {}", args.f);
     println!("Proof System: {:?}", args.system);
 
     // Generate the proof based on the selected proof system.
@@ -85,19 +97,22 @@ fn create_proof_fixture(
     vk: &SP1VerifyingKey,
     system: ProofSystem,
 ) {
-    // Deserialize the public values.
+    // Deserialize the public values, an array with the single proof that was verified.
     let bytes = proof.public_values.as_slice();
-    // let MerkleProof { n, a, b } = MerkleProof::abi_decode(bytes, false).unwrap();
-    // mock a, b n
-    let a = 1;
-    let b = 2;
-    let n = 3;
+    let decoded = Array::<PublicValuesStruct>::abi_decode(bytes, true)
+        .expect("failed to decode public values");
+    let [decoded] = decoded.as_slice() else {
+        panic!("expected the public values of a single proof");
+    };
+    print!("{}", decoded);
 
     // Create the testing fixture so we can test things end-to-end.
-    let fixture = SP1FibonacciProofFixture {
-        a,
-        b,
-        n,
+    let fixture = SP1MerkleProofFixture {
+        root: decoded.proof.root.to_string(),
+        key: decoded.proof.key.to_string(),
+        value: BigUint::from_bytes_le(&decoded.proof.value).to_string(),
+        value_len: decoded.proof.value.len(),
+        fnc: decoded.proof.fnc,
         vkey: vk.bytes32().to_string(),
         public_values: format!("0x{}", hex::encode(bytes)),
         proof: format!("0x{}", hex::encode(proof.bytes())),
@@ -10,11 +10,17 @@
 //! RUST_LOG=info cargo run --release -- --prove
 //! ```
 
-// use alloy_sol_types::SolType;
+use alloy_sol_types::{sol_data::Array, SolType};
+use arbo_sandbox_script::{check_proofs, read_merkleproof_from_file, TreeArgs};
 use clap::Parser;
 use hex::ToHex;
-use smtverifier::{MerkleProof, MerkleProofFromFile};
-use sp1_sdk::{ProverClient, SP1Stdin};
+use num_bigint::BigUint;
+use serde::Deserialize;
+use smtverifier::{
+    Blake3, MerkleProof, OutputMode, ProofSystem, PublicValuesStruct, RootKeyPublicValuesStruct,
+    RootPublicValuesStruct, RootValueHashPublicValuesStruct, Statement, TreeConfig,
+};
+use sp1_sdk::{HashableKey, ProverClient, SP1Proof, SP1Stdin};
 use std::time::Instant;
 use std::{fs::File, io::BufReader, io::Write};
 
@@ -34,26 +40,67 @@ struct Args {
     #[clap(long)]
     verify: bool,
 
+    /// Proof files to verify, all in the same program execution. Can be given several
+    /// times, and the public values list the proofs in the same order.
     #[clap(short, default_value = "merkleproof.json")]
-    f: String,
-}
+    f: Vec<String>,
 
-fn read_merkleproof_from_file(path: &str) -> Result<MerkleProof, serde_json::Error> {
-    // Open the file in read-only mode
-    let file = File::open(path).expect("Failed to open file");
-    let reader = BufReader::new(file);
+    #[clap(flatten)]
+    tree: TreeArgs,
 
-    // Deserialize JSON to the intermediate struct (MerkleProofFromFile)
-    let proof: MerkleProofFromFile = serde_json::from_reader(reader)?;
+    /// Parts of each proof committed as public values: full, root-key (hides the value),
+    /// root-value-hash (hides the key, and the value unless it can be guessed from its
+    /// unsalted hash) or root.
+    #[clap(long, default_value = "full")]
+    output: OutputMode,
 
-    let proof = MerkleProof {
-        root: proof.root,
-        key: proof.key,
-        value: proof.value,
-        siblings: proof.siblings,
-    };
+    /// Hash of the verifying key of the program, as printed by --generate. --verify needs
+    /// it unless --fixture is given, in which case the vkey of the fixture is trusted.
+    #[clap(long)]
+    vkey: Option<String>,
+
+    /// EVM fixture written by the evm binary, verified by --verify instead of proof.json.
+    #[clap(long)]
+    fixture: Option<String>,
 
-    Ok(proof)
+    /// Proof system of the fixture: groth16 or plonk.
+    #[clap(long, default_value = "groth16")]
+    system: ProofSystem,
+
+    /// Verifying key of the SP1 circuit the proof was wrapped with. Defaults to the one
+    /// shipped with sp1-verifier, which only matches proofs of the same SP1 version.
+    #[clap(long)]
+    circuit_vk: Option<String>,
+
+    /// Root that --verify expects the proof to be against, as a decimal number like in the
+    /// proof files. With --key and --value, it replaces the proofs of -f as the statement
+    /// the public values must match, in the tree given by --max-levels and --key-len.
+    #[clap(long, requires = "key")]
+    root: Option<BigUint>,
+
+    /// Key that --verify expects the proof to be for, as a decimal number.
+    #[clap(long, requires = "root")]
+    key: Option<BigUint>,
+
+    /// Value that --verify expects the key to hold, as a decimal number. It is taken as
+    /// long as the key or its significant bytes, whichever is longer, unless --value-len is
+    /// given. Without it, the proof must show that the key is not in the tree.
+    #[clap(long, requires = "root")]
+    value: Option<BigUint>,
+
+    /// Length in bytes of --value, like `valueLen` in the proof files. Values that end in
+    /// zero bytes need it.
+    #[clap(long, requires = "value")]
+    value_len: Option<usize>,
+}
+
+/// The fields of a fixture written by the evm binary that are needed to verify it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProofFixture {
+    vkey: String,
+    public_values: String,
+    proof: String,
 }
 
 fn main() {
@@ -68,43 +115,44 @@ fn main() {
         std::process::exit(1);
     }
 
-    // Setup the prover client.
-    let client = ProverClient::new();
+    if args.verify {
+        verify(&args);
+        return;
+    }
 
-    let proof = read_merkleproof_from_file(&args.f).expect("Error reading or deserializing JSON");
+    let (config, proofs) = read_proofs(&args);
 
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
-    stdin.write(&proof);
+    stdin.write(&config);
+    stdin.write(&args.output);
+    stdin.write(&proofs);
 
-    println!("passed proof to program stdin: {:?}", proof);
+    println!(
+        "passed {} proofs to program stdin: {:?}",
+        proofs.len(),
+        proofs
+    );
 
     if args.execute {
+        // Setup the prover client.
+        let client = ProverClient::new();
+
         // Execute the program
         let start_time = Instant::now();
-        let (_output, report) = client.execute(FIBONACCI_ELF, stdin).run().unwrap();
+        let (output, report) = client.execute(FIBONACCI_ELF, stdin).run().unwrap();
         println!("Program executed successfully.");
         println!("Time elapsed: {:?}", start_time.elapsed());
 
-        // // Read the output.
-        // let decoded = PublicValuesStruct::abi_decode(output.as_slice(), true).unwrap();
-        // let PublicValuesStruct { n, a, b, root } = decoded;
-        // println!("n: {}", n);
-        // println!("a: {}", a);
-        // println!("b: {}", b);
-        // println!("root: {:?}", root);
-        // println!("offset: {}", args.offset);
-
-        // let (expected_a, expected_b) = fibonacci_lib::fibonacci(n);
-        // assert_eq!(a, expected_a + args.offset);
-        // assert_eq!(b, expected_b + args.offset);
-        // println!("Values are correct!");
+        // Read the output.
+        print_public_values(args.output, output.as_slice());
 
         // Record the number of cycles executed.
         println!("Number of cycles: {}", report.total_instruction_count());
     } else if args.generate {
         // Setup the program for proving.
-        let (pk, _) = client.setup(FIBONACCI_ELF);
+        let client = ProverClient::new();
+        let (pk, vk) = client.setup(FIBONACCI_ELF);
 
         // Generate the proof
         let proof = client
@@ -114,40 +162,122 @@ fn main() {
             .expect("failed to generate proof");
 
         println!("Successfully generated proof! {:#?}", proof);
+        println!("vkey: {}", vk.bytes32());
 
         save_proof_to_json(&proof).expect("failed to save proof to disk");
-    } else if args.verify {
-        // Setup the program for proving.
-        let (_, vk) = client.setup(FIBONACCI_ELF);
-
-        let proof = load_proof_from_json();
-
-        println!("loaded proof.json from disk: {:#?}", proof);
-
-        let mut proof = proof;
-        proof.stdin = sp1_sdk::SP1Stdin::default();
-        println!("mutated proof, now is: {:#?}", proof);
-
-        // Verify the proof.
-        client.verify(&proof, &vk).expect("failed to verify proof");
-        println!("Successfully verified proof!");
-        println!(
-            "I don't know which offset was used:
-            on the proof.public_values i can see n, a, b but not the offset,
-            yet i know the proof is valid"
-        );
-
-        println!(
-            "public_values slice is {}",
-            proof.public_values.encode_hex::<String>()
-        );
-
-        // let decoded = PublicValuesStruct::abi_decode(proof.public_values.as_slice(), true).unwrap();
-        // let PublicValuesStruct { n, a, b, root } = decoded;
-        // println!(
-        //     "so in public_values i see n={}, a={}, b={}, root={:?}",
-        //     n, a, b, root
-        // );
+    }
+}
+
+/// Reads the proofs of -f along with the config of the tree they belong to.
+fn read_proofs(args: &Args) -> (TreeConfig<Blake3>, Vec<MerkleProof>) {
+    let proofs: Vec<MerkleProof> = args.f.iter().map(read_merkleproof_from_file).collect();
+    let config = args.tree.config(proofs[0].siblings.len());
+    check_proofs(&config, &args.f, &proofs);
+
+    (config, proofs)
+}
+
+/// Verifies the groth16 or plonk proof in proof.json or in a fixture, and checks that its
+/// public values prove the expected statements.
+fn verify(args: &Args) {
+    let (system, proof, public_values, fixture_vkey) = match &args.fixture {
+        Some(path) => {
+            let (proof, public_values, vkey) = load_proof_from_fixture(path);
+            (args.system, proof, public_values, Some(vkey))
+        }
+        None => {
+            let proof = load_proof_from_json();
+            let system = match proof.proof {
+                SP1Proof::Groth16(_) => ProofSystem::Groth16,
+                SP1Proof::Plonk(_) => ProofSystem::Plonk,
+                _ => {
+                    eprintln!("Error: only groth16 and plonk proofs can be verified");
+                    std::process::exit(1);
+                }
+            };
+            (system, proof.bytes(), proof.public_values.to_vec(), None)
+        }
+    };
+
+    // The vkey is what ties the proof to this program. The one of a fixture is only as
+    // trusted as the fixture itself: one written for another program verifies just as well.
+    let Some(vkey) = args.vkey.clone().or(fixture_vkey) else {
+        eprintln!("Error: --verify needs --vkey, as printed by --generate");
+        std::process::exit(1);
+    };
+    let circuit_vk = match &args.circuit_vk {
+        Some(path) => std::fs::read(path).expect("Failed to read circuit vk"),
+        None => system.circuit_vk().to_vec(),
+    };
+
+    // Verify the proof.
+    if let Err(e) = smtverifier::verify_snark(system, &proof, &public_values, &vkey, &circuit_vk) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Successfully verified proof!");
+
+    println!(
+        "public_values slice is {}",
+        public_values.encode_hex::<String>()
+    );
+
+    // A valid proof only shows that the program accepted some Merkle proofs, so check
+    // that they are the ones expected, in the expected tree.
+    let (config, expected): (TreeConfig<Blake3>, Vec<Statement>) = match (&args.root, &args.key) {
+        (Some(root), Some(key)) => {
+            let Some(max_levels) = args.tree.max_levels else {
+                eprintln!("Error: --root needs --max-levels, the tree the proof must be for");
+                std::process::exit(1);
+            };
+            let config = args.tree.config(max_levels);
+            let statement = Statement::from_biguints(
+                root,
+                key,
+                args.value.as_ref(),
+                args.value_len,
+                config.key_len,
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Error: invalid expected statement: {}", e);
+                std::process::exit(1);
+            });
+            (config, vec![statement])
+        }
+        _ => {
+            let (config, proofs) = read_proofs(args);
+            (config, proofs.iter().map(Statement::from).collect())
+        }
+    };
+    if let Err(e) = args.output.check_all(&public_values, &config, &expected) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Public values match the expected proofs!");
+
+    print_public_values(args.output, &public_values);
+}
+
+/// Decodes the public values committed by the program in `mode` and prints them, one
+/// entry per proof.
+fn print_public_values(mode: OutputMode, bytes: &[u8]) {
+    fn decode<T: SolType<RustType = T> + ToString>(
+        bytes: &[u8],
+    ) -> Result<Vec<String>, alloy_sol_types::Error> {
+        Array::<T>::abi_decode(bytes, true).map(|v| v.iter().map(T::to_string).collect())
+    }
+    let decoded = match mode {
+        OutputMode::Full => decode::<PublicValuesStruct>(bytes),
+        OutputMode::RootKey => decode::<RootKeyPublicValuesStruct>(bytes),
+        OutputMode::RootValueHash => decode::<RootValueHashPublicValuesStruct>(bytes),
+        OutputMode::Root => decode::<RootPublicValuesStruct>(bytes),
+    };
+    for (i, values) in decoded
+        .expect("failed to decode public values")
+        .iter()
+        .enumerate()
+    {
+        print!("[{}] {}", i, values);
     }
 }
 
@@ -172,3 +302,18 @@ fn load_proof_from_json() -> sp1_sdk::SP1ProofWithPublicValues {
         serde_json::from_reader(file).expect("Failed to deserialize proof");
     proof
 }
+
+/// Reads the proof bytes, public values and vkey of a fixture written by the evm binary.
+fn load_proof_from_fixture(path: &str) -> (Vec<u8>, Vec<u8>, String) {
+    let file = File::open(path).expect("Failed to open fixture file");
+    let fixture: ProofFixture =
+        serde_json::from_reader(BufReader::new(file)).expect("Failed to deserialize fixture");
+    println!("loaded fixture for vkey {}", fixture.vkey);
+
+    let decode = |s: &str| hex::decode(s.trim_start_matches("0x")).expect("Invalid hex in fixture");
+    (
+        decode(&fixture.proof),
+        decode(&fixture.public_values),
+        fixture.vkey,
+    )
+}
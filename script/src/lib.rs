@@ -0,0 +1,69 @@
+//! Code shared by the binaries of the script: reading the proof files written by arbo and
+//! the options of the tree they belong to.
+
+use smtverifier::{Blake3, MerkleProof, MerkleProofFromFile, TreeConfig};
+use std::path::Path;
+use std::{fs::File, io::BufReader};
+
+/// The tree the proofs belong to.
+#[derive(clap::Args, Debug)]
+pub struct TreeArgs {
+    /// Levels of the tree the proofs belong to, arbo's `MaxLevels`. Defaults to the
+    /// number of siblings in the proofs, as arbo writes one per level.
+    #[clap(long)]
+    pub max_levels: Option<usize>,
+
+    /// Key length in bytes. Defaults to the bytes needed to address every level.
+    #[clap(long)]
+    pub key_len: Option<usize>,
+}
+
+impl TreeArgs {
+    /// Returns the config of the tree, with `levels` levels unless --max-levels is given.
+    pub fn config(&self, levels: usize) -> TreeConfig<Blake3> {
+        let mut config = TreeConfig::new(self.max_levels.unwrap_or(levels), Blake3);
+        if let Some(key_len) = self.key_len {
+            config.key_len = key_len;
+        }
+        config
+    }
+}
+
+/// Reads a proof file written by `go run .`, exiting if it can't be read or parsed.
+pub fn read_merkleproof_from_file(path: impl AsRef<Path>) -> MerkleProof {
+    let path = path.as_ref();
+    let proof = File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            let proof: MerkleProofFromFile =
+                serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())?;
+            MerkleProof::try_from(proof).map_err(|e| e.to_string())
+        });
+    proof.unwrap_or_else(|e| {
+        eprintln!("Error: can't read {}: {}", path.display(), e);
+        std::process::exit(1);
+    })
+}
+
+/// Checks the proofs natively, so that an invalid proof is reported with its cause instead
+/// of as an opaque panic inside the zkVM. Exits on the first invalid one, printing how it
+/// went through the levels.
+pub fn check_proofs(
+    config: &TreeConfig<Blake3>,
+    files: &[impl AsRef<Path>],
+    proofs: &[MerkleProof],
+) {
+    for (f, proof) in files.iter().zip(proofs) {
+        if let Err(e) = smtverifier::verify_circom_proof(config, proof) {
+            eprintln!(
+                "Error: invalid merkle proof in {}: {}",
+                f.as_ref().display(),
+                e
+            );
+            if let Ok(trace) = smtverifier::trace_circom_proof(config, proof) {
+                eprint!("{}", trace);
+            }
+            std::process::exit(1);
+        }
+    }
+}